@@ -2,7 +2,7 @@ import Quill from 'quill';
 import 'quill/dist/quill.snow.css';
 import qrcode from 'qrcode-generator';
 import { jsPDF } from "jspdf";
-import init, { VDFComputer, VDFProof } from './wasm/vdf_wasm.js';
+import init, { ClassGroupVDF, VDFComputer, VDFProof, get_version, modulus_profiles, set_log_level, sha3_256_hex } from './wasm/vdf_wasm.js';
 
 // --- Helper function for SHA-256 ---
 async function sha256(message) {
@@ -129,32 +129,316 @@ class EpochProof {
     };
     this.iterations = iterations;
     this.epochDuration = epochDuration;
-    this.timestamp = new Date().toISOString();
+    this.timestamp = now().toISOString();
+    this.hashVersion = HASH_VERSION;
     this.hash = null;
   }
 
   async computeHash() {
-    const content = {
-      epochNumber: this.epochNumber,
-      previousHash: this.previousHash,
-      deltas: this.deltas,
-      vdfY: this.vdfProof.y,
-      iterations: this.iterations
-    };
-    this.hash = await sha256(JSON.stringify(content));
+    this.hash = await epochHash(this);
     return this.hash;
   }
 }
 
+// --- Canonical Hashing ---
+// Version 0 (no hashVersion field) hashed JSON.stringify output, whose key order
+// depends on how each object happened to be built. Version 1 hashes canonicalJson.
+const HASH_VERSION = 1;
+
+// Canonical JSON: object keys sorted by UTF-16 code unit, undefined members
+// dropped, no whitespace, strings and finite numbers as JSON.stringify writes them.
+//   canonicalJson({ b: [1, 'x'], a: { d: null, c: true } }) === '{"a":{"c":true,"d":null},"b":[1,"x"]}'
+//   sha256: 582165beb9dc97d37ab12d65c9d5ff785e7a736798669e05ffc7ce355e8b3054
+function canonicalJson(value) {
+  if (value === null || typeof value !== 'object') {
+    if (typeof value === 'number' && !Number.isFinite(value)) throw new Error('Cannot hash a non-finite number');
+    return JSON.stringify(value);
+  }
+  if (Array.isArray(value)) {
+    return `[${value.map(item => (item === undefined ? 'null' : canonicalJson(item))).join(',')}]`;
+  }
+  return `{${Object.keys(value)
+    .filter(key => value[key] !== undefined)
+    .sort()
+    .map(key => `${JSON.stringify(key)}:${canonicalJson(value[key])}`)
+    .join(',')}}`;
+}
+
+function hashEncoding(hashVersion) {
+  if (hashVersion === undefined) return JSON.stringify;
+  if (hashVersion === 1) return canonicalJson;
+  throw new Error(`Unsupported hash version ${hashVersion}`);
+}
+
+async function epochHash(epoch) {
+  return await sha256(hashEncoding(epoch.hashVersion)(epochHashContent(epoch)));
+}
+
+// The fields of an epoch covered by its hash. Optional records are only
+// included when present so older documents keep hashing the same way.
+function epochHashContent(epoch) {
+  const content = {
+    epochNumber: epoch.epochNumber,
+    previousHash: epoch.previousHash,
+    deltas: epoch.deltas,
+    vdfY: epoch.vdfProof.y,
+    iterations: epoch.iterations
+  };
+  if (epoch.difficultyChange) content.difficultyChange = epoch.difficultyChange;
+  if (epoch.resumedFrom) content.resumedFrom = epoch.resumedFrom;
+  if (epoch.events) content.events = epoch.events;
+  if (epoch.declaredRate !== undefined) content.declaredRate = epoch.declaredRate;
+  if (epoch.cadenceHash) content.cadenceHash = epoch.cadenceHash;
+  if (epoch.seeded) content.seeded = epoch.seeded;
+  if (epoch.hashVersion !== undefined) content.hashVersion = epoch.hashVersion;
+  return content;
+}
+
+// Dual-hash mode keeps a second, SHA3-256 chain next to the SHA-256 one so the
+// hashes outlive a break of either. Both signatures still rely on SHA-2, see
+// signAndSaveDocument. Each epoch's SHA3 digest covers the same
+// content as its hash plus the previous SHA3 digest. Mirrors epoch_hash_sha3.
+function epochHashSha3(epoch, previous) {
+  return sha3_256_hex(canonicalJson({ ...epochHashContent(epoch), previousHashSha3: previous.hashSha3 ?? null }));
+}
+
+// --- Attestation Packages ---
+const ATTESTATION_PACKAGE_FORMAT = 'bitquill-attestation-package';
+const ATTESTATION_INSTRUCTIONS = [
+  'BitQuill Attestation Package',
+  '',
+  'This file bundles a BitQuill document with the evidence of how it was written:',
+  '  document      - the full document, including its VDF proof chain and signature',
+  '  text          - the plain text of the document',
+  '  verification  - the verification report produced by the author',
+  '  authorship    - the authorship analysis of the proof chain',
+  '  publicKey     - the author\'s ECDSA P-384 public key (JWK)',
+  '',
+  'To check it independently, open BitQuill, import this file and run verification.',
+  'Every epoch\'s VDF proof and hash is recomputed, and the signature is checked',
+  'against the embedded public key. Do not rely on the bundled report alone.'
+].join('\n');
+
+// --- Settings ---
+const LS_SETTINGS = 'bitquill-settings';
+const DEFAULT_SETTINGS = {
+  strictVerification: false,
+  epochSeconds: 10,
+  desktopNotifications: false,
+  collectMetrics: false,
+  logLevel: 'warn', // VDF console logging: 'error', 'warn' or 'debug'
+  attestedCopy: false, // Attach the writing epochs to text copied from the editor
+  stopOnFirstError: false,
+  maxFindings: 0, // 0 checks the whole chain
+  commitPolicy: 'auto', // 'auto' binds edits every epoch, 'manual' only after Ctrl+Enter,
+  // 'paragraph' once a paragraph looks finished (see requestParagraphCommit)
+  dualHash: false, // Also chain and sign SHA3-256 digests
+  typingCadence: false, // Bind a histogram of keystroke intervals (never the keys) into each epoch
+  recordEnvironment: false, // Record the browser's user agent, platform and language in provenance
+  timeZone: 'local', // 'local', 'UTC' or a fixed offset such as '+05:30'
+  timeFormat: 'medium', // 'short', 'medium', 'long' or 'iso'
+  vdfBackend: 'wesolowski-rsa' // VDF group of new documents, a key of VDF_BACKENDS
+};
+// Numeric settings are clamped to these ranges however they were entered or stored
+const SETTING_BOUNDS = { epochSeconds: [1, 600] };
+
+function clampSettings(values) {
+  const clamped = { ...values };
+  Object.entries(SETTING_BOUNDS).forEach(([key, [min, max]]) => {
+    if (!(key in clamped)) return;
+    const value = Number(clamped[key]);
+    clamped[key] = Number.isFinite(value) ? Math.min(Math.max(value, min), max) : DEFAULT_SETTINGS[key];
+  });
+  return clamped;
+}
+
+let settings = clampSettings({ ...DEFAULT_SETTINGS, ...JSON.parse(localStorage.getItem(LS_SETTINGS)) });
+
+// Auditor mode (?mode=auditor) overrides these for its tab only
+const AUDITOR_SETTINGS = { strictVerification: true, stopOnFirstError: false, maxFindings: 0 };
+let auditing = false;
+
+// Saves only the keys that changed, over what is stored, so overrides of this
+// session never end up in the user's saved settings
+function updateSettings(changes) {
+  changes = clampSettings(changes);
+  settings = { ...settings, ...changes, ...(auditing ? AUDITOR_SETTINGS : {}) };
+  const saved = Object.fromEntries(Object.entries(changes).filter(([key]) => !(auditing && key in AUDITOR_SETTINGS)));
+  localStorage.setItem(LS_SETTINGS, JSON.stringify({ ...JSON.parse(localStorage.getItem(LS_SETTINGS)), ...saved }));
+}
+
+// --- Seeded Mode ---
+// Opening BitQuill with ?seed=<text> makes documents reproducible for demos,
+// screenshots and tests. Time is simulated: it starts at an instant derived from
+// the seed and advances exactly one epoch length per committed epoch. Iterations
+// are fixed instead of calibrated, and VDF proofs are deterministic already.
+// Signatures are not, since WebCrypto ECDSA draws a fresh nonce every time.
+// Seeded documents record the seed in their provenance and mark every epoch, so
+// verification flags them as demos rather than evidence of when they were written.
+const SEEDED_ITERATIONS = 10000;
+const SEEDED_BASE_TIME = Date.UTC(2024, 0, 1);
+const seed = new URLSearchParams(window.location.search).get('seed');
+let simulatedTime = seed === null ? null : seededStartTime(seed);
+
+// FNV-1a of the seed, spread over a year in whole seconds
+function seededStartTime(seed) {
+  let hash = 0x811c9dc5;
+  for (const char of seed) hash = Math.imul(hash ^ char.codePointAt(0), 0x01000193) >>> 0;
+  return SEEDED_BASE_TIME + (hash % (365 * 86400)) * 1000;
+}
+
+// The time recorded in documents, simulated in seeded mode
+function now() {
+  return simulatedTime === null ? new Date() : new Date(simulatedTime);
+}
+
+// Inputs in the settings dialog name their setting with data-setting
+function bindSettingsForm(form, onChange) {
+  form.querySelectorAll('[data-setting]').forEach(input => {
+    const key = input.dataset.setting;
+    if (input.type === 'checkbox') input.checked = settings[key];
+    else input.value = settings[key];
+    input.onchange = () => {
+      const value = input.type === 'checkbox' ? input.checked : input.type === 'number' ? Number(input.value) : input.value;
+      updateSettings({ [key]: value });
+      if (input.type === 'number') input.value = settings[key];
+      onChange(key);
+    };
+  });
+}
+
+// Documents always store UTC ISO timestamps; only what is shown on screen and
+// in reports follows the display time zone and format settings.
+function displayOffsetMinutes(date) {
+  if (settings.timeZone === 'UTC') return 0;
+  const match = /^([+-])(\d{2}):(\d{2})$/.exec(settings.timeZone);
+  if (!match) return -date.getTimezoneOffset();
+  return (match[1] === '-' ? -1 : 1) * (Number(match[2]) * 60 + Number(match[3]));
+}
+
+function formatTime(value, part = 'datetime') {
+  const date = new Date(value);
+  const offset = displayOffsetMinutes(date);
+  // Shifting by the offset and formatting as UTC applies any zone the same way
+  const shifted = new Date(date.getTime() + offset * 60000);
+  if (settings.timeFormat === 'iso') {
+    const sign = offset < 0 ? '-' : '+';
+    const abs = Math.abs(offset);
+    const suffix = offset === 0 ? 'Z' : `${sign}${String(Math.floor(abs / 60)).padStart(2, '0')}:${String(abs % 60).padStart(2, '0')}`;
+    const iso = shifted.toISOString().replace('Z', suffix);
+    return part === 'date' ? iso.slice(0, 10) : part === 'time' ? iso.slice(11) : iso;
+  }
+  const options = { timeZone: 'UTC' };
+  if (part !== 'time') options.dateStyle = settings.timeFormat;
+  if (part !== 'date') options.timeStyle = settings.timeFormat === 'short' ? 'short' : 'medium';
+  const zone = settings.timeZone === 'local' ? '' : settings.timeZone === 'UTC' ? ' UTC' : ` UTC${settings.timeZone}`;
+  return shifted.toLocaleString(undefined, options) + (part === 'date' ? '' : zone);
+}
+
 // --- Local Storage Management ---
 const LS_PREFIX = 'bitquill-doc-';
+const LS_VERSION_PREFIX = 'bitquill-version-';
 const LS_KEYS = 'bitquill-keys';
+const LS_AUDIT_LOG = 'bitquill-audit-log';
+
+// --- Modulus Registry ---
+const LS_MODULI = 'bitquill-moduli';
+const DEFAULT_MODULUS_ID = 'rsa-2048';
+
+// Built-in profiles from the VDF module plus moduli registered in this browser,
+// e.g. an organisation's own: { id, name, trust, modulusHex }. Documents name
+// the modulus they use and are only verified against moduli listed here.
+function modulusRegistry() {
+  const custom = JSON.parse(localStorage.getItem(LS_MODULI)) || [];
+  return [...JSON.parse(modulus_profiles()), ...custom];
+}
+
+function computerForModulus(modulusId) {
+  const profile = modulusRegistry().find(p => p.id === modulusId);
+  if (!profile) throw new Error(`Modulus "${modulusId}" is not in the trusted registry.`);
+  const computer = profile.modulusHex ? VDFComputer.with_modulus(profile.modulusHex) : VDFComputer.with_profile(profile.id);
+  return { profile, computer };
+}
+
+// The groups a document's VDF can run in, recorded as provenance.vdfBackend.
+// RSA needs a modulus nobody can factor; a class group of a public discriminant
+// has no trusted setup, but its squarings are far slower, hence the smaller counts.
+const RSA_BACKEND = 'wesolowski-rsa';
+const CLASS_GROUP_BACKEND = 'wesolowski-class-group';
+const VDF_BACKENDS = {
+  [RSA_BACKEND]: { baselineIterations: 10000, fallbackIterations: 100000, seededIterations: SEEDED_ITERATIONS },
+  [CLASS_GROUP_BACKEND]: { baselineIterations: 1000, fallbackIterations: 2000, seededIterations: 1000 }
+};
+
+function computerForProvenance(provenance) {
+  const backend = provenance?.vdfBackend || RSA_BACKEND;
+  if (backend === CLASS_GROUP_BACKEND) return { profile: null, computer: new ClassGroupVDF() };
+  if (backend !== RSA_BACKEND) throw new Error(`Unsupported VDF backend "${backend}".`);
+  return computerForModulus(provenance?.modulusId || DEFAULT_MODULUS_ID);
+}
+
+function vdfProvenanceFor(backend) {
+  if (backend === CLASS_GROUP_BACKEND) {
+    return { vdfVersion: get_version(), vdfBackend: backend, discriminantFingerprint: new ClassGroupVDF().discriminant_fingerprint() };
+  }
+  return { vdfVersion: get_version(), vdfBackend: RSA_BACKEND, modulusId: DEFAULT_MODULUS_ID, modulusFingerprint: new VDFComputer().modulus_fingerprint() };
+}
+
+// Append-only record of trust decisions the user made in this browser.
+function appendAuditLog(event, details) {
+  const log = JSON.parse(localStorage.getItem(LS_AUDIT_LOG)) || [];
+  log.push({ timestamp: new Date().toISOString(), event, ...details });
+  localStorage.setItem(LS_AUDIT_LOG, JSON.stringify(log));
+}
+
+// Documents record the fingerprint of the modulus they were produced with. A
+// different modulus changes the trust assumptions, so the user has to accept it.
+function confirmModulus(docData) {
+  const fingerprint = docData.provenance?.modulusFingerprint;
+  if (!fingerprint || !vdfProvenance) return true;
+  const trusted = new VDFComputer().modulus_fingerprint();
+  if (fingerprint === trusted) return true;
+  const accepted = confirm(
+    `WARNING: "${docData.title}" was produced with a different VDF modulus.\n\n` +
+    `Document modulus: ${fingerprint}\nTrusted modulus:  ${trusted}\n\n` +
+    `Its time proofs only hold if nobody knows the factorization of that modulus. Open it anyway?`
+  );
+  if (accepted) appendAuditLog('modulus-accepted', { title: docData.title, fingerprint });
+  return accepted;
+}
+
+// Optional artifacts that describe the author rather than the document: typing
+// cadence and the producing environment. The document hash leaves them out (see
+// documentHashContent), so removing them keeps the export verifiable. Documents
+// from before that hashing change are exported whole.
+function scrubForExport(docData) {
+  if (docData.version !== DOCUMENT_VERSION) return docData;
+  const { environment, ...provenance } = docData.provenance || {};
+  const scrubbed = { ...docData, ...(docData.provenance ? { provenance } : {}) };
+  const cadenceEpochs = docData.proofChain.filter(epoch => epoch.cadence).length;
+  if (cadenceEpochs > 0 && confirm(
+    `"${docData.title}" holds typing cadence for ${cadenceEpochs} epochs. Remove it from the export? ` +
+    `Verification is unaffected; the author analysis will have less to go on.`
+  )) {
+    scrubbed.proofChain = docData.proofChain.map(({ cadence, ...epoch }) => epoch);
+  }
+  if (environment || scrubbed.proofChain !== docData.proofChain) {
+    appendAuditLog('export-scrubbed', { title: docData.title, cadenceEpochs: scrubbed.proofChain === docData.proofChain ? 0 : cadenceEpochs, environment: Boolean(environment) });
+  }
+  return scrubbed;
+}
 
 async function signAndSaveDocument() {
   if (!userKeys) {
     showToast("Please generate or load signing keys first.", "error");
     return;
   }
+  // Transfers certify the signed version they name, which a new signature replaces
+  if (appState.custody.length > 0) {
+    if (!confirm(`This document carries ${appState.custody.length} custody transfers of the version you received. ` +
+      `Signing a new version leaves them out; keep the received file as proof of custody. Sign anyway?`)) return;
+    appState.custody = [];
+  }
   let key = appState.currentDocumentKey || LS_PREFIX + Date.now();
   appState.currentDocumentKey = key;
 
@@ -163,11 +447,16 @@ async function signAndSaveDocument() {
   const signature = await cryptoHelpers.sign(docData.metadata.documentHash, userKeys.signingKeys.privateKey);
   docData.metadata.signature = signature;
   docData.metadata.publicKey = await cryptoHelpers.exportKey(userKeys.signingKeys.publicKey);
+  // WebCrypto has no signature scheme over SHA-3: ECDSA P-384 hashes the SHA3
+  // digest with SHA-384 too, so a SHA-2 break reaches this signature as well
+  if (docData.metadata.documentHashSha3) {
+    docData.metadata.signatureSha3 = await cryptoHelpers.sign(docData.metadata.documentHashSha3, userKeys.signingKeys.privateKey);
+  }
 
   await saveToLocal(key, docData, userKeys.encryptionKey);
   appState.isDirty = false;
   appState.loadedMetadata = docData.metadata; // Update loaded metadata on save
-  document.getElementById('last-saved').textContent = `Saved ${new Date().toLocaleTimeString()}`;
+  emitDocumentEvent('saved', { docData });
   showToast('Document signed and saved', 'success');
 }
 
@@ -215,23 +504,232 @@ async function loadKeys() {
   }
 }
 
+// --- Document Stores ---
+// Every store holds the same encrypted records ({ metadata, payload }) keyed by
+// document key, so the encryption layer above does not care where they live.
+const LS_STORE = 'bitquill-store';
+
+// Imports above this size ask for confirmation before being parsed
+const LARGE_IMPORT_BYTES = 50 * 1024 * 1024;
+
+const localStorageStore = {
+  name: 'Browser storage',
+  async put(key, record) {
+    localStorage.setItem(key, JSON.stringify(record));
+  },
+  async get(key) {
+    return JSON.parse(localStorage.getItem(key));
+  },
+  async list(prefix = LS_PREFIX) {
+    const records = [];
+    for (let i = 0; i < localStorage.length; i++) {
+      const key = localStorage.key(i);
+      if (key.startsWith(prefix)) {
+        try {
+          records.push({ key, record: JSON.parse(localStorage.getItem(key)) });
+        } catch (e) { console.error(`Could not parse doc metadata for key ${key}:`, e); }
+      }
+    }
+    return records;
+  },
+  async remove(key) {
+    localStorage.removeItem(key);
+  }
+};
+
+// IndexedDB is not bound by the ~5MB localStorage quota, which long documents
+// with many epochs run into quickly.
+const indexedDbStore = {
+  name: 'IndexedDB',
+  db: null,
+  async open() {
+    if (this.db) return this.db;
+    this.db = await new Promise((resolve, reject) => {
+      const request = indexedDB.open('bitquill', 1);
+      request.onupgradeneeded = () => request.result.createObjectStore('documents');
+      request.onsuccess = () => resolve(request.result);
+      request.onerror = () => reject(request.error);
+    });
+    return this.db;
+  },
+  async transaction(mode, action) {
+    const db = await this.open();
+    return new Promise((resolve, reject) => {
+      const request = action(db.transaction('documents', mode).objectStore('documents'));
+      request.onsuccess = () => resolve(request.result);
+      request.onerror = () => reject(request.error);
+    });
+  },
+  // A write only counts once its transaction commits, not when the request succeeds
+  async put(key, record) {
+    const db = await this.open();
+    await new Promise((resolve, reject) => {
+      const tx = db.transaction('documents', 'readwrite');
+      tx.objectStore('documents').put(record, key);
+      tx.oncomplete = () => resolve();
+      tx.onerror = () => reject(tx.error);
+      tx.onabort = () => reject(tx.error);
+    });
+  },
+  async get(key) {
+    return (await this.transaction('readonly', store => store.get(key))) || null;
+  },
+  // Keys and records are read by one cursor, so a concurrent write cannot pair them up wrongly
+  async list(prefix = LS_PREFIX) {
+    const db = await this.open();
+    return new Promise((resolve, reject) => {
+      const records = [];
+      const range = IDBKeyRange.bound(prefix, prefix + '\uffff');
+      const request = db.transaction('documents', 'readonly').objectStore('documents').openCursor(range);
+      request.onsuccess = () => {
+        const cursor = request.result;
+        if (!cursor) return resolve(records);
+        records.push({ key: cursor.key, record: cursor.value });
+        cursor.continue();
+      };
+      request.onerror = () => reject(request.error);
+    });
+  },
+  async remove(key) {
+    await this.transaction('readwrite', store => store.delete(key));
+  }
+};
+
+const documentStores = { local: localStorageStore, indexeddb: indexedDbStore };
+
+function activeStore() {
+  return documentStores[localStorage.getItem(LS_STORE)] || localStorageStore;
+}
+
+// Moves every document and archived version between stores. Records are only
+// removed from the old store once all of them have been written to the new one.
+async function migrateStore(from, to) {
+  const records = [...await from.list(LS_PREFIX), ...await from.list(LS_VERSION_PREFIX)];
+  for (const { key, record } of records) await to.put(key, record);
+  for (const { key } of records) await from.remove(key);
+  return records.length;
+}
+
+// Other tabs announce their saves here so an open copy is never overwritten blindly
+const documentChannel = new BroadcastChannel('bitquill-documents');
+
+// Summarizes how two copies of a document relate: whether one history extends
+// the other, where they diverge and whether their signed metadata agrees.
+function compareDocuments(a, b) {
+  const { base, localOnly, externalOnly } = compareChains(a.proofChain, b.proofChain);
+  let relation = 'diverged';
+  if (localOnly.length === 0 && externalOnly.length === 0) relation = 'identical history';
+  else if (localOnly.length === 0) relation = 'first is a prefix of second';
+  else if (externalOnly.length === 0) relation = 'second is a prefix of first';
+  const same = (field) => JSON.stringify(a.metadata?.[field]) === JSON.stringify(b.metadata?.[field]);
+  return {
+    relation,
+    base,
+    firstOnly: localOnly,
+    secondOnly: externalOnly,
+    sameDocumentHash: same('documentHash'),
+    sameSigner: same('publicKey'),
+    sameTitle: a.title === b.title
+  };
+}
+
+// --- Incremental Backups ---
+// A document delta carries only the epochs added after a known head, plus the
+// current content and signed metadata, so a backup can be brought up to date
+// without re-exporting the whole history.
+const DOCUMENT_DELTA_FORMAT = 'bitquill-document-delta';
+
+function diffSince(docData, baseHash) {
+  const baseIndex = docData.proofChain.findIndex(epoch => epoch.hash === baseHash);
+  if (baseIndex === -1) throw new Error('The base head is not part of this document.');
+  const { proofChain, ...rest } = docData;
+  return { format: DOCUMENT_DELTA_FORMAT, baseHash, epochs: proofChain.slice(baseIndex + 1), ...rest };
+}
+
+function applyDelta(base, delta) {
+  const head = base.proofChain[base.proofChain.length - 1];
+  if (head.hash !== delta.baseHash) throw new Error('The delta does not start at this document\'s head.');
+  if (delta.epochs.length > 0 && delta.epochs[0].previousHash !== head.hash) {
+    throw new Error('The delta\'s first epoch does not follow this document\'s head.');
+  }
+  const { format, baseHash, epochs, ...rest } = delta;
+  return { ...rest, proofChain: [...base.proofChain, ...epochs] };
+}
+
+// Splits two chains at their last shared epoch, the base of a three-way comparison.
+function compareChains(local, external) {
+  let common = 0;
+  while (common < local.length && common < external.length && local[common].hash === external[common].hash) common++;
+  return {
+    base: common > 0 ? local[common - 1] : null,
+    localOnly: local.slice(common),
+    externalOnly: external.slice(common)
+  };
+}
+
+// --- Versions ---
+// A stored document is never overwritten with a different head or deleted
+// outright: the previous record is first copied, still encrypted, under its
+// document key and head hash so earlier attested states can be restored from
+// the file browser. Documents share heads (every one starts at genesis, and
+// imported copies share their original's), so the head alone is not unique.
+// Only the newest versions of each document are kept, and archiving is best
+// effort: a full store must never stop the document itself from being saved.
+const MAX_VERSIONS_PER_DOCUMENT = 20;
+
+async function archiveVersion(key, newHeadHash = null) {
+  try {
+    const previous = await activeStore().get(key);
+    if (!previous || !previous.metadata.latestHash || previous.metadata.latestHash === newHeadHash) return;
+    const versionKey = `${LS_VERSION_PREFIX}${key}-${previous.metadata.latestHash}`;
+    await activeStore().put(versionKey, { ...previous, metadata: { ...previous.metadata, documentKey: key } });
+    await pruneVersions(key);
+  } catch (e) {
+    console.warn("Could not archive the previous version of", key, e);
+  }
+}
+
+async function pruneVersions(documentKey) {
+  const versions = (await listVersions()).filter(version => version.documentKey === documentKey);
+  for (const version of versions.slice(MAX_VERSIONS_PER_DOCUMENT)) {
+    await activeStore().remove(version.key);
+  }
+}
+
+async function listVersions() {
+  const versions = (await activeStore().list(LS_VERSION_PREFIX))
+    .map(({ key, record }) => ({ key, ...record.metadata }));
+  return versions.sort((a, b) => new Date(b.timestamp) - new Date(a.timestamp));
+}
+
+async function restoreVersion(versionKey) {
+  const version = await activeStore().get(versionKey);
+  const { documentKey, ...metadata } = version.metadata;
+  await archiveVersion(documentKey, metadata.latestHash);
+  await activeStore().put(documentKey, { ...version, metadata });
+  return documentKey;
+}
+
 async function saveToLocal(key, data, encryptionKey) {
   try {
-    const unencryptedMetadata = { title: data.title, timestamp: data.timestamp };
+    const unencryptedMetadata = { title: data.title, timestamp: data.timestamp, latestHash: data.metadata.latestHash };
     const encryptedPayload = await cryptoHelpers.encrypt(data, encryptionKey);
     const storableData = {
       metadata: unencryptedMetadata,
       payload: encryptedPayload
     };
-    localStorage.setItem(key, JSON.stringify(storableData));
+    await archiveVersion(key, unencryptedMetadata.latestHash);
+    await activeStore().put(key, storableData);
+    documentChannel.postMessage({ key, timestamp: data.timestamp });
   } catch (e) {
     console.error("Error saving to local storage:", e);
     showToast("Could not save document. Storage may be full.", "error");
+    emitDocumentEvent('save-failed', { error: e });
   }
 }
 
 async function loadFromLocal(key, encryptionKey) {
-  const storableData = JSON.parse(localStorage.getItem(key));
+  const storableData = await activeStore().get(key);
   if (!storableData || !storableData.payload) return null;
   try {
     return await cryptoHelpers.decrypt(storableData.payload, encryptionKey);
@@ -242,27 +740,152 @@ async function loadFromLocal(key, encryptionKey) {
   }
 }
 
-function listLocalDocs() {
-  const docs = [];
-  for (let i = 0; i < localStorage.length; i++) {
-    const key = localStorage.key(i);
-    if (key.startsWith(LS_PREFIX)) {
-      try {
-        const doc = JSON.parse(localStorage.getItem(key));
-        if (doc && doc.metadata && doc.metadata.title) {
-          docs.push({ key, title: doc.metadata.title, timestamp: doc.metadata.timestamp });
-        }
-      } catch (e) { console.error(`Could not parse doc metadata for key ${key}:`, e); }
+async function listLocalDocs() {
+  const docs = (await activeStore().list())
+    .filter(({ record }) => record && record.metadata && record.metadata.title)
+    .map(({ key, record }) => ({ key, title: record.metadata.title, timestamp: record.metadata.timestamp }));
+  return docs.sort((a, b) => new Date(b.timestamp) - new Date(a.timestamp));
+}
+
+async function deleteFromLocal(key) {
+  await archiveVersion(key);
+  await activeStore().remove(key);
+}
+
+// --- Document Events ---
+// Changes to the open document are announced here. UI pieces subscribe to the
+// events they display instead of being updated by whoever changed the state.
+//   epoch-created { epoch }    pending-changed { count }    verified { results }
+//   saved { docData }          loaded { docData }           created {}
+//   save-failed { error }      vdf-failed { error }         exported { kind }
+const documentEvents = new EventTarget();
+
+function emitDocumentEvent(type, detail = {}) {
+  documentEvents.dispatchEvent(new CustomEvent(type, { detail }));
+}
+
+function onDocumentEvent(type, handler) {
+  documentEvents.addEventListener(type, (e) => handler(e.detail));
+}
+
+// --- Root Feed ---
+// Every save appends the chain head to a per-document feed the author can publish,
+// so others can archive the heads over time. Maps document key to entries.
+// Entries are signed by the author, so the feed also works as a head log that
+// stands on its own if the document itself is lost.
+const LS_ROOT_FEED = 'bitquill-root-feed';
+const HEAD_LOG_FORMAT = 'bitquill-head-log';
+
+async function appendRootFeed(key, docData, signingKeys) {
+  const feeds = JSON.parse(localStorage.getItem(LS_ROOT_FEED)) || {};
+  const entries = feeds[key] || [];
+  const entry = {
+    timestamp: docData.timestamp,
+    headHash: docData.metadata.latestHash,
+    epochCount: docData.metadata.epochCount,
+    documentHash: docData.metadata.documentHash
+  };
+  if (entries.length > 0 && entries[entries.length - 1].headHash === entry.headHash) return;
+  entry.signature = await cryptoHelpers.sign(canonicalJson(entry), signingKeys.privateKey);
+  feeds[key] = [...entries, entry];
+  localStorage.setItem(LS_ROOT_FEED, JSON.stringify(feeds));
+}
+
+// The signed entry for a head, if this browser saved the document at that head
+function signedHeadEntry(key, headHash) {
+  const entries = (JSON.parse(localStorage.getItem(LS_ROOT_FEED)) || {})[key] || [];
+  return entries.find(entry => entry.headHash === headHash) || null;
+}
+
+function headLog(key, title, publicKey) {
+  const entries = (JSON.parse(localStorage.getItem(LS_ROOT_FEED)) || {})[key] || [];
+  return { format: HEAD_LOG_FORMAT, version: 1, title, publicKey, entries };
+}
+
+// Checks every entry's signature and that heads only move forward. With a chain,
+// also checks that each logged head is the epoch the log says it is.
+async function verifyHeadLog(log, chain = null) {
+  const errors = [];
+  const publicKey = await cryptoHelpers.importKey(log.publicKey, 'ECDSA', ['verify']);
+  for (const [i, { signature, ...entry }] of log.entries.entries()) {
+    if (!signature || !(await cryptoHelpers.verify(canonicalJson(entry), signature, publicKey))) {
+      errors.push(`Entry ${i}: Missing or invalid signature.`);
+    }
+    if (i > 0 && entry.epochCount < log.entries[i - 1].epochCount) {
+      errors.push(`Entry ${i}: Epoch count goes backwards.`);
+    }
+    if (chain && chain[entry.epochCount - 1]?.hash !== entry.headHash) {
+      errors.push(`Entry ${i}: Head ${entry.headHash.substring(0, 16)}... is not epoch ${entry.epochCount - 1} of this document.`);
     }
   }
-  return docs.sort((a, b) => new Date(b.timestamp) - new Date(a.timestamp));
+  return { valid: errors.length === 0, errors };
+}
+
+// Compares two signed logs of the same document. Two different heads signed for
+// the same epoch count, or the same moment, are equivocation: the author showed
+// different histories to different people. Both logs must verify on their own first.
+async function compareHeadLogs(mine, theirs) {
+  const problems = [];
+  if (!sameKey(mine.publicKey, theirs.publicKey)) problems.push('The logs are signed by different keys.');
+  for (const [name, log] of [['Your log', mine], ['Their log', theirs]]) {
+    const { errors } = await verifyHeadLog(log);
+    problems.push(...errors.map(error => `${name}: ${error}`));
+  }
+  if (problems.length > 0) return { comparable: false, problems, equivocations: [], shared: 0 };
+
+  const equivocations = [];
+  let shared = 0;
+  const byCount = new Map(mine.entries.map(entry => [entry.epochCount, entry]));
+  const byTime = new Map(mine.entries.map(entry => [entry.timestamp, entry]));
+  for (const entry of theirs.entries) {
+    const match = byCount.get(entry.epochCount) || byTime.get(entry.timestamp);
+    if (!match) continue;
+    if (match.headHash === entry.headHash) shared++;
+    else equivocations.push({ epochCount: entry.epochCount, timestamp: entry.timestamp, mine: match.headHash, theirs: entry.headHash });
+  }
+  return { comparable: true, problems, equivocations, shared };
+}
+
+// The feed as JSON Feed 1.1, newest first, ready to host as a static file
+function rootFeed(key, title) {
+  const entries = (JSON.parse(localStorage.getItem(LS_ROOT_FEED)) || {})[key] || [];
+  return {
+    version: 'https://jsonfeed.org/version/1.1',
+    title: `BitQuill chain heads: ${title}`,
+    items: [...entries].reverse().map(entry => ({
+      id: entry.headHash,
+      date_published: entry.timestamp,
+      content_text: `Epoch ${entry.epochCount - 1} head ${entry.headHash}, document hash ${entry.documentHash}`,
+      _bitquill: entry
+    }))
+  };
+}
+
+// --- Metrics ---
+// Opt-in performance counters, kept in memory for this tab only.
+const metrics = {};
+
+function recordMetric(name, value) {
+  if (!settings.collectMetrics) return;
+  const m = metrics[name] || (metrics[name] = { count: 0, sum: 0, min: Infinity, max: -Infinity, last: 0 });
+  m.count++;
+  m.sum += value;
+  m.min = Math.min(m.min, value);
+  m.max = Math.max(m.max, value);
+  m.last = value;
 }
 
-function deleteFromLocal(key) {
-  localStorage.removeItem(key);
+function metricsReport() {
+  return Object.fromEntries(Object.entries(metrics).map(([name, m]) => [name, { ...m, mean: m.sum / m.count }]));
 }
 
 // --- Modern UI Functions ---
+// Anything read from a document (titles, findings that quote it, epoch fields)
+// is escaped before it goes into markup: imported documents are untrusted.
+function escapeHtml(value) {
+  return String(value).replace(/[&<>"']/g, char => ({ '&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;', "'": '&#39;' })[char]);
+}
+
 function showToast(message, type = 'info') {
   const container = document.getElementById('toast-container');
   const toast = document.createElement('div');
@@ -276,6 +899,77 @@ function showToast(message, type = 'info') {
   }, 5000);
 }
 
+function downloadFile(filename, content, type = 'application/json') {
+  const blob = new Blob([content], { type });
+  const url = URL.createObjectURL(blob);
+  const a = document.createElement('a');
+  a.href = url;
+  a.download = filename;
+  a.click();
+  URL.revokeObjectURL(url);
+}
+
+// One compact JSON object per epoch so shell pipelines and log ingestion can
+// stream a document's history. Edits are reduced to a hash unless requested.
+async function epochsToJsonl(chain, includeContent = false) {
+  const lines = [];
+  for (const epoch of chain) {
+    const record = {
+      epochNumber: epoch.epochNumber,
+      timestamp: epoch.timestamp,
+      hash: epoch.hash,
+      previousHash: epoch.previousHash || null,
+      iterations: epoch.iterations ?? null,
+      vdfOutput: epoch.vdfProof ? epoch.vdfProof.y : null,
+      contentHash: epoch.deltas ? await sha256(JSON.stringify(epoch.deltas)) : null
+    };
+    if (includeContent && epoch.deltas) record.deltas = epoch.deltas;
+    lines.push(JSON.stringify(record));
+  }
+  return lines.join('\n') + '\n';
+}
+
+// One row per epoch for spreadsheets. Proof values are base64 big-endian, so
+// the bit length of the Fiat-Shamir prime l is read off its bytes.
+function epochsToCsv(chain) {
+  const bitLength = (base64) => {
+    const bytes = atob(base64);
+    return bytes.length === 0 ? 0 : (bytes.length - 1) * 8 + bytes.charCodeAt(0).toString(2).length;
+  };
+  const rows = [['sequence', 'system_time', 'iterations', 'proof_l_bits', 'interval_since_prev']];
+  chain.forEach((epoch, i) => {
+    const interval = i > 0 ? (new Date(epoch.timestamp) - new Date(chain[i - 1].timestamp)) / 1000 : '';
+    rows.push([
+      epoch.epochNumber,
+      epoch.timestamp,
+      epoch.iterations ?? '',
+      epoch.vdfProof ? bitLength(epoch.vdfProof.l) : '',
+      interval
+    ]);
+  });
+  return rows.map(row => row.join(',')).join('\n') + '\n';
+}
+
+// Resolves with the parsed file, or null if the picker is dismissed
+function pickJsonFile() {
+  return new Promise((resolve, reject) => {
+    const input = document.createElement('input');
+    input.type = 'file';
+    input.accept = '.json,application/json';
+    input.addEventListener('change', () => {
+      const file = input.files[0];
+      if (!file) { resolve(null); return; }
+      file.text().then(text => resolve(JSON.parse(text))).catch(reject);
+    });
+    input.addEventListener('cancel', () => resolve(null));
+    input.click();
+  });
+}
+
+function safeFilename(title) {
+  return title.replace(/[^a-z0-9]/gi, '_').toLowerCase();
+}
+
 function updateWordCount(quill) {
   const text = quill.getText();
   const words = text.trim().split(/\s+/).filter(word => word.length > 0).length;
@@ -288,6 +982,7 @@ function showVerificationModal() {
   document.getElementById('modal-backdrop').classList.add('show');
   document.getElementById('verification-modal').classList.add('show');
   document.querySelector('.verification-progress').style.display = 'block';
+  document.getElementById('verification-live').innerHTML = '';
   document.getElementById('verification-results').style.display = 'none';
 }
 
@@ -295,6 +990,19 @@ function hideVerificationModal() {
   document.getElementById('modal-backdrop').classList.remove('show');
   document.getElementById('verification-modal').classList.remove('show');
   document.getElementById('export-pdf-btn').style.display = 'none';
+  document.getElementById('export-package-btn').style.display = 'none';
+}
+
+// Lists findings while verification is still running
+function appendVerificationDetail({ epochNumber, errors, warnings }) {
+  const listEl = document.getElementById('verification-live');
+  [...errors.map(message => ['error', message]), ...warnings.map(message => ['warning', message])]
+    .forEach(([severity, message]) => {
+      const item = document.createElement('div');
+      item.className = `verification-detail ${severity}`;
+      item.textContent = epochNumber === null || message.startsWith('Epoch') ? message : `Epoch ${epochNumber}: ${message}`;
+      listEl.appendChild(item);
+    });
 }
 
 function updateVerificationProgress(percent, status) {
@@ -308,26 +1016,250 @@ function updateVerificationProgress(percent, status) {
   statusEl.textContent = status;
 }
 
+// --- Tutorial ---
+// The first-run walkthrough advances when the app emits the event each step
+// waits for, so it follows what the user actually did.
+const LS_TUTORIAL_DONE = 'bitquill-tutorial-done';
+const TUTORIAL_STEPS = [
+  { event: 'epoch-created', text: 'Type a sentence. When the timer in the header runs out, a VDF proof binds your edits into a new epoch.' },
+  { event: 'saved', text: 'Epoch created: the number in the header went up. Now sign and save the document (generate signing keys first if you have none).' },
+  { event: 'verified', text: 'Saved. Run verification to recompute every epoch\'s proof and check your signature.' },
+  { event: 'exported', text: 'Verified. Export a verified PDF or an attestation package to share the proof.' }
+];
+
+// --- Command Palette ---
+// Recorded macros: { name: [command names] }
+const LS_MACROS = 'bitquill-macros';
+
+// Subsequence match: every query character must appear in order. Higher is
+// better; consecutive and word-start matches score extra, -1 means no match.
+function fuzzyScore(query, text) {
+  const q = query.toLowerCase();
+  const t = text.toLowerCase();
+  let score = 0;
+  let last = -1;
+  for (const ch of q) {
+    const index = t.indexOf(ch, last + 1);
+    if (index === -1) return -1;
+    score += 1 + (index === last + 1 ? 2 : 0) + (index === 0 || t[index - 1] === ' ' ? 3 : 0);
+    last = index;
+  }
+  return score;
+}
+
+// --- Proof Chain Queries ---
+// Text inserted during an epoch, in the order it was typed.
+function epochText(epoch) {
+  return (epoch.deltas || [])
+    .flatMap(delta => (delta && delta.ops) || [])
+    .map(op => (typeof op.insert === 'string' ? op.insert : ''))
+    .join('');
+}
+
+// Epochs stamped in [from, to)
+function epochsBetween(chain, from, to) {
+  return chain.filter(epoch => {
+    const time = new Date(epoch.timestamp);
+    return time >= from && time < to;
+  });
+}
+
+// A bare date means that whole local day, so as an end bound it runs up to the
+// following midnight. Date.parse would read it as UTC midnight instead.
+function parseQueryDate(text, isEnd) {
+  const day = text.match(/^(\d{4})-(\d{2})-(\d{2})$/);
+  if (!day) return new Date(text);
+  return new Date(Number(day[1]), Number(day[2]) - 1, Number(day[3]) + (isEnd ? 1 : 0));
+}
+
+function epochsMatching(chain, text) {
+  const needle = text.toLowerCase();
+  return chain.filter(epoch => epochText(epoch).toLowerCase().includes(needle));
+}
+
+function epochsInRange(chain, first, last) {
+  return chain.filter(epoch => epoch.epochNumber >= first && epoch.epochNumber <= last);
+}
+
+// "12-30" or "12" as { from, to }; anything else means the whole chain
+function parseEpochRange(text) {
+  const match = text.trim().match(/^(\d+)(?:\s*-\s*(\d+))?$/);
+  if (!match) return null;
+  const from = Number(match[1]);
+  return { from, to: match[2] === undefined ? from : Number(match[2]) };
+}
+
+// Accepts "12-30" (epoch numbers), "2025-01-01..2025-01-31" (dates) or plain text.
+function queryEpochs(chain, query) {
+  const trimmed = query.trim();
+  if (!trimmed) return chain;
+  const range = trimmed.match(/^(\d+)\s*-\s*(\d+)$/);
+  if (range) return epochsInRange(chain, Number(range[1]), Number(range[2]));
+  const dates = trimmed.split('..').map(date => date.trim());
+  if (dates.length === 2 && !isNaN(Date.parse(dates[0])) && !isNaN(Date.parse(dates[1]))) {
+    return epochsBetween(chain, parseQueryDate(dates[0], false), parseQueryDate(dates[1], true));
+  }
+  return epochsMatching(chain, trimmed);
+}
+
+// Every occurrence of `text` in the typed history, including text that was later
+// deleted from the live document.
+function searchHistory(chain, text) {
+  const needle = text.toLowerCase();
+  const matches = [];
+  if (!needle) return matches;
+  chain.forEach(epoch => {
+    const haystack = epochText(epoch);
+    let at = haystack.toLowerCase().indexOf(needle);
+    while (at !== -1) {
+      const start = Math.max(0, at - 30);
+      const end = Math.min(haystack.length, at + needle.length + 30);
+      matches.push({ epochNumber: epoch.epochNumber, timestamp: epoch.timestamp, snippet: haystack.slice(start, end) });
+      at = haystack.toLowerCase().indexOf(needle, at + needle.length);
+    }
+  });
+  return matches;
+}
+
+// --- Blame ---
+// Replays every epoch's deltas to find which epoch typed each character of the
+// current document. Returns null if the replay does not line up with `text`.
+function computeBlame(chain, text) {
+  const owners = [0]; // A new document starts as a single newline from genesis
+  chain.forEach(epoch => {
+    (epoch.deltas || []).forEach(delta => {
+      let index = 0;
+      ((delta && delta.ops) || []).forEach(op => {
+        if (op.retain !== undefined) {
+          index += op.retain;
+        } else if (op.insert !== undefined) {
+          const length = typeof op.insert === 'string' ? op.insert.length : 1;
+          owners.splice(index, 0, ...new Array(length).fill(epoch.epochNumber));
+          index += length;
+        } else if (op.delete !== undefined) {
+          owners.splice(index, op.delete);
+        }
+      });
+    });
+  });
+  return owners.length === text.length ? owners : null;
+}
+
+// One entry per paragraph: the epoch that started it and the latest epoch that revised it.
+function blameParagraphs(chain, text) {
+  const owners = computeBlame(chain, text);
+  if (!owners) return null;
+  const timestamps = new Map(chain.map(epoch => [epoch.epochNumber, epoch.timestamp]));
+  const paragraphs = [];
+  let start = 0;
+  text.split('\n').slice(0, -1).forEach(line => {
+    // The newline of the last paragraph is the document's original one, so only
+    // fall back to it for empty paragraphs
+    const lineOwners = owners.slice(start, start + Math.max(line.length, 1));
+    const introduced = Math.min(...lineOwners);
+    const revised = Math.max(...lineOwners);
+    paragraphs.push({ text: line, introduced, revised, timestamp: timestamps.get(introduced) });
+    start += line.length + 1;
+  });
+  return paragraphs;
+}
+
+function renderBlame(chain, text) {
+  const proofDisplay = document.getElementById('proof-display');
+  const paragraphs = blameParagraphs(chain, text);
+  if (!paragraphs) {
+    proofDisplay.innerHTML = '<p style="color: var(--text-secondary);">History does not replay to the current text; blame is unavailable.</p>';
+    return;
+  }
+  proofDisplay.innerHTML = '';
+  paragraphs.filter(p => p.text.trim().length > 0).forEach(p => {
+    const entry = document.createElement('div');
+    entry.style.cssText = 'padding: 4px 2px; border-bottom: 1px solid var(--bg-tertiary);';
+    const revision = p.revised !== p.introduced ? `, revised #${escapeHtml(p.revised)}` : '';
+    entry.innerHTML = `<div style="color: var(--primary-color);">EPOCH #${escapeHtml(p.introduced)}${revision} <span style="color: var(--text-secondary);">${formatTime(p.timestamp)}</span></div>`;
+    const line = document.createElement('div');
+    line.textContent = p.text.length > 80 ? p.text.substring(0, 80) + '...' : p.text;
+    entry.appendChild(line);
+    proofDisplay.appendChild(entry);
+  });
+}
+
+// --- Outline ---
+// Sections start at Markdown headings ("## Title") or editor heading lines
+// (levels[i] is the header level of paragraph i) and span the epochs that
+// wrote their paragraphs.
+function outlineSections(chain, text, levels = []) {
+  const paragraphs = blameParagraphs(chain, text);
+  if (!paragraphs) return null;
+  const sections = [];
+  let current = null;
+  let start = 0;
+  paragraphs.forEach((p, i) => {
+    const markdown = /^(#{1,6})\s+(.*)$/.exec(p.text);
+    const level = levels[i] || (markdown ? markdown[1].length : 0);
+    if (level) {
+      current = { title: markdown ? markdown[2] : p.text, level, index: start, from: p.introduced, to: p.revised };
+      sections.push(current);
+    } else if (current && p.text.trim().length > 0) {
+      current.from = Math.min(current.from, p.introduced);
+      current.to = Math.max(current.to, p.revised);
+    }
+    start += p.text.length + 1;
+  });
+  return sections;
+}
+
+// Recent epoch durations and difficulty, so the user can see the difficulty
+// tracking the target epoch length before relying on the timing.
+const EPOCH_CHART_SIZE = 30;
+
+function sparkline(values, target = null) {
+  const width = 240, height = 40;
+  const max = Math.max(...values, target || 0) || 1;
+  const y = (value) => (height - 2 - (value / max) * (height - 4)).toFixed(1);
+  const points = values.map((value, i) => `${((i / Math.max(values.length - 1, 1)) * width).toFixed(1)},${y(value)}`).join(' ');
+  const targetLine = target ? `<line x1="0" x2="${width}" y1="${y(target)}" y2="${y(target)}" class="sparkline-target"/>` : '';
+  return `<svg class="sparkline" viewBox="0 0 ${width} ${height}" preserveAspectRatio="none">${targetLine}<polyline points="${points}"/></svg>`;
+}
+
+function renderEpochCharts(chain) {
+  const recent = chain.slice(1).slice(-EPOCH_CHART_SIZE).filter(epoch => epoch.iterations > 0);
+  const chartsEl = document.getElementById('epoch-charts');
+  if (recent.length < 2) {
+    chartsEl.innerHTML = '';
+    return;
+  }
+  const durations = recent.map(epoch => epoch.epochDuration || 0);
+  const iterations = recent.map(epoch => epoch.iterations);
+  chartsEl.innerHTML = `
+    <div class="epoch-chart-label">Epoch length, last ${recent.length} (target ${settings.epochSeconds}s, latest ${durations[durations.length - 1].toFixed(1)}s)</div>
+    ${sparkline(durations, settings.epochSeconds)}
+    <div class="epoch-chart-label">Iterations (latest ${iterations[iterations.length - 1].toLocaleString()})</div>
+    ${sparkline(iterations)}`;
+}
+
 function renderProofChain(chain, mode = 'live', verificationErrors = []) {
+  renderEpochCharts(chain);
   const proofDisplay = document.getElementById('proof-display');
   proofDisplay.innerHTML = '';
   const findErrorForEpoch = (index) => verificationErrors.find(e => e.includes(`at epoch ${index}`) || e.includes(`Epoch ${index}:`)) || null;
-  chain.forEach((epoch, index) => {
+  const query = document.getElementById('proof-query').value;
+  queryEpochs(chain, query).forEach((epoch) => {
     let statusIcon, statusColor, statusTitle;
     if (mode === 'verified') {
-      const error = findErrorForEpoch(index);
+      const error = findErrorForEpoch(epoch.epochNumber);
       const isValid = !error;
       statusIcon = isValid ? '✓' : '✗';
       statusColor = isValid ? 'var(--primary-color)' : '#ff4444';
-      statusTitle = isValid ? 'Verified' : `Error: ${error}`;
+      statusTitle = isValid ? 'Verified' : `Error: ${escapeHtml(error)}`;
     } else {
       statusIcon = '●';
       statusColor = 'var(--text-secondary)';
       statusTitle = 'Epoch created';
     }
-    const label = epoch.epochNumber === 0 ? 'GENESIS' : `EPOCH #${epoch.epochNumber}`;
-    const hash = (epoch.hash || '').substring(0, 16) + '...';
-    const duration = (epoch.epochDuration || 0).toFixed(1) + 's';
+    const label = epoch.epochNumber === 0 ? 'GENESIS' : `EPOCH #${escapeHtml(epoch.epochNumber)}`;
+    const hash = escapeHtml(String(epoch.hash || '').substring(0, 16)) + '...';
+    const duration = Number(epoch.epochDuration || 0).toFixed(1) + 's';
     const durationInfo = epoch.epochNumber > 0 ? `<span style="color: var(--text-secondary); font-size: 11px; margin-left: 1em;">(${duration})</span>` : '';
     const entryHTML = `<div title="${statusTitle}" style="padding: 4px 2px; border-bottom: 1px solid var(--bg-tertiary);"><span style="color: ${statusColor}; font-weight: bold; margin-right: 8px;">${statusIcon}</span><span>${label}: ${hash}</span>${durationInfo}</div>`;
     proofDisplay.innerHTML += entryHTML;
@@ -783,9 +1715,74 @@ function calculateEnhancedHumanAuthorshipScore(proofChain) {
   };
 }
 
+// Per-epoch size change and Shannon entropy of the inserted text. An epoch that
+// grows by a block of varied text faster than anyone types, well beyond its
+// neighbours, most likely came from a paste or generated insertion. Held keys
+// and repeated characters stay below the entropy bound.
+const BULK_INSERT_MIN_CHARS = 50;
+const BULK_INSERT_NEIGHBOUR_FACTOR = 5;
+const BULK_INSERT_MIN_ENTROPY = 3; // bits per character; English prose is around 4
+
+function shannonEntropy(text) {
+  const counts = {};
+  for (const char of text) counts[char] = (counts[char] || 0) + 1;
+  return Object.values(counts).reduce((sum, count) => {
+    const p = count / text.length;
+    return sum - p * Math.log2(p);
+  }, 0);
+}
+
+function analyzeContentEntropy(proofChain) {
+  const epochs = proofChain.slice(1).map(epoch => {
+    const text = epochText(epoch);
+    const deleted = (epoch.deltas || [])
+      .flatMap(delta => (delta && delta.ops) || [])
+      .reduce((sum, op) => sum + (op.delete || 0), 0);
+    return {
+      epochNumber: epoch.epochNumber,
+      inserted: text.length,
+      lengthDelta: text.length - deleted,
+      entropy: text.length > 0 ? shannonEntropy(text) : 0,
+      charsPerSecond: text.length / Math.max(epoch.epochDuration || 0, 1)
+    };
+  });
+  const flagged = epochs.filter((epoch, i) => {
+    const neighbours = [epochs[i - 1], epochs[i + 1]].filter(Boolean);
+    const neighbourMean = neighbours.reduce((sum, n) => sum + Math.max(n.lengthDelta, 0), 0) / Math.max(neighbours.length, 1);
+    return epoch.inserted >= BULK_INSERT_MIN_CHARS &&
+      epoch.charsPerSecond > EXAM_MAX_CHARS_PER_SECOND &&
+      epoch.entropy >= BULK_INSERT_MIN_ENTROPY &&
+      epoch.lengthDelta > Math.max(neighbourMean, 1) * BULK_INSERT_NEIGHBOUR_FACTOR;
+  });
+  return { epochs, flagged };
+}
+
+function checkContentEntropy(epochs, results) {
+  analyzeContentEntropy(epochs).flagged.forEach(epoch => {
+    results.warnings.push(`Epoch ${epoch.epochNumber}: ${epoch.inserted} characters appeared at ${epoch.charsPerSecond.toFixed(0)} chars/s, faster than anyone types. They were likely pasted or generated.`);
+  });
+}
+
+// Text typed by hand needs roughly a keystroke per character. Epochs that
+// recorded a cadence but gained far more characters than keystrokes did not
+// get their text from the keyboard.
+function analyzeCadence(proofChain) {
+  const recorded = proofChain.slice(1).filter(epoch => epoch.cadence);
+  const unexplained = recorded
+    .map(epoch => ({
+      epochNumber: epoch.epochNumber,
+      keystrokes: epoch.cadence.counts.reduce((sum, count) => sum + count, 0) + 1,
+      inserted: epochText(epoch).length
+    }))
+    .filter(epoch => epoch.inserted > epoch.keystrokes * 2 + BULK_INSERT_MIN_CHARS);
+  return { recorded: recorded.length, unexplained };
+}
+
 // Update the display function to show enhanced metrics
 function showEnhancedAuthorshipAnalysis(proofChain) {
   const analysis = calculateEnhancedHumanAuthorshipScore(proofChain);
+  const content = analyzeContentEntropy(proofChain);
+  const cadence = analyzeCadence(proofChain);
   const percentage = Math.round(analysis.humanScore * 100);
 
   return `
@@ -806,6 +1803,15 @@ function showEnhancedAuthorshipAnalysis(proofChain) {
       <div style="margin-top: 12px; font-size: 12px; color: var(--text-secondary);">
         ${analysis.metrics.totalEpochs} epochs analyzed, ${analysis.metrics.totalChars} total characters
       </div>
+      ${content.flagged.length > 0 ? `
+      <div class="details" style="margin-top: 12px; color: #ffaa00;">
+        ${content.flagged.map(e => `<div>Epoch ${escapeHtml(e.epochNumber)}: +${e.lengthDelta} chars at ${e.charsPerSecond.toFixed(0)} chars/s, entropy ${e.entropy.toFixed(2)} bits/char (likely pasted or generated)</div>`).join('')}
+      </div>` : ''}
+      ${cadence.recorded > 0 ? `
+      <div style="margin-top: 12px; font-size: 12px; color: var(--text-secondary);">Typing cadence recorded in ${cadence.recorded} epochs</div>
+      <div class="details" style="color: #ffaa00;">
+        ${cadence.unexplained.map(e => `<div>Epoch ${escapeHtml(e.epochNumber)}: ${e.inserted} chars from ${e.keystrokes} keystrokes</div>`).join('')}
+      </div>` : ''}
     </div>
   `;
 }
@@ -823,62 +1829,697 @@ function updateUITimers(elapsed, targetSeconds, progress) {
   }
 }
 
-async function verifyDocument(documentData, updateProgress) {
-  const results = { valid: true, errors: [], verifiedEpochs: 0, totalEpochs: 0, signatureValid: false };
-  try {
-    // 1. Verify VDF Chain
-    await init();
-    const computer = new VDFComputer();
-    const epochs = documentData.proofChain;
-    results.totalEpochs = epochs.length - 1;
-    for (let i = 1; i < epochs.length; i++) {
-      const epoch = epochs[i];
-      if (updateProgress) updateProgress((i / (epochs.length - 1)) * 90, `Verifying epoch ${i}...`);
-      if (epoch.previousHash !== epochs[i - 1].hash) { results.errors.push(`Epoch ${i}: Broken chain.`); results.valid = false; continue; }
-      const vdfProof = new VDFProof(epoch.vdfProof.y, epoch.vdfProof.pi, epoch.vdfProof.l, epoch.vdfProof.r, BigInt(epoch.iterations));
-      if (!(await computer.verify_proof(epoch.previousHash, vdfProof))) { results.errors.push(`Epoch ${i}: Invalid VDF proof.`); results.valid = false; } else { results.verifiedEpochs++; }
-      const content = { epochNumber: epoch.epochNumber, previousHash: epoch.previousHash, deltas: epoch.deltas, vdfY: epoch.vdfProof.y, iterations: epoch.iterations };
-      if (epoch.hash !== await sha256(JSON.stringify(content))) { results.errors.push(`Epoch ${i}: Hash mismatch.`); results.valid = false; }
+// Iterations may only change between epochs when the new epoch records why.
+// Older documents never recorded changes, so an unexplained change is a warning.
+function checkDifficultyChange(epoch, previous, index, results) {
+  const change = epoch.difficultyChange;
+  if (change) {
+    if (change.previousIterations !== previous.iterations || change.newIterations !== epoch.iterations) {
+      results.errors.push(`Epoch ${index}: Difficulty change record does not match iterations.`);
+      results.valid = false;
     }
+  } else if (index > 1 && epoch.iterations !== previous.iterations) {
+    results.warnings.push(`Epoch ${index}: Iterations changed from ${previous.iterations} to ${epoch.iterations} without a recorded reason.`);
+  }
+}
 
-    // 2. Verify Signature
-    if (updateProgress) updateProgress(95, 'Verifying signature...');
-    const { publicKey, signature, documentHash } = documentData.metadata;
-    if (publicKey && signature && documentHash) {
-      const pubKey = await cryptoHelpers.importKey(publicKey, 'ECDSA', ['verify']);
-      results.signatureValid = await cryptoHelpers.verify(documentHash, signature, pubKey);
-      if (!results.signatureValid) {
-        results.errors.push("Author signature is invalid.");
-      }
-    } else {
-      results.errors.push("Document is not signed.");
+// Undo/redo records must point at a delta recorded in the same epoch.
+function checkEpochEvents(epoch, index, results) {
+  (epoch.events || []).forEach(event => {
+    if (SESSION_MARKERS.includes(event.type)) return; // Checked by checkTimedSessions
+    results.retractions += event.type === 'undo' ? 1 : 0;
+    if (!['undo', 'redo'].includes(event.type) || !(event.delta >= 0 && event.delta < (epoch.deltas || []).length)) {
+      results.errors.push(`Epoch ${index}: Event log references an unknown edit.`);
+      results.valid = false;
     }
-
-    results.valid = results.errors.length === 0;
-    if (updateProgress) updateProgress(100, 'Verification complete.');
-  } catch (error) {
-    results.valid = false;
-    results.errors.push(error.message);
-  }
-  return results;
+  });
 }
 
-// Declare state variables and helper functions in the global scope
-let userKeys = null;
+// Timed sessions open with a session-start marker declaring their length in
+// minutes and close with a session-end marker. Edits bound after the declared
+// window (plus one grace period for the epoch in flight) fail verification.
+const SESSION_MARKERS = ['session-start', 'session-end'];
+const SESSION_GRACE_SECONDS = 60;
+// Exam sessions also cap how fast text can appear, which catches pasting around the lockdown
+const EXAM_MAX_CHARS_PER_SECOND = 15;
+
+function checkTimedSessions(epochs, results) {
+  results.timedSessions = [];
+  let open = null;
+  epochs.forEach((epoch, i) => {
+    if (open && i > open.start) {
+      open.writingSeconds += epoch.epochDuration || 0;
+      const elapsed = (new Date(epoch.timestamp) - new Date(epochs[open.start].timestamp)) / 1000;
+      if ((epoch.deltas || []).length > 0 && elapsed > open.minutes * 60 + SESSION_GRACE_SECONDS) {
+        results.errors.push(`Epoch ${i}: Edits fall outside the timed session that started at epoch ${open.start}.`);
+      }
+      if (open.exam && epochText(epoch).length > Math.max(epoch.epochDuration || 0, 1) * EXAM_MAX_CHARS_PER_SECOND) {
+        results.errors.push(`Epoch ${i}: Text appeared faster than the exam typing limit.`);
+      }
+    }
+    (epoch.events || []).forEach(event => {
+      if (event.type === 'session-start') {
+        if (open) results.errors.push(`Epoch ${i}: Timed session starts inside another session.`);
+        if (!(event.minutes > 0)) results.errors.push(`Epoch ${i}: Timed session declares no duration.`);
+        open = { start: i, end: null, minutes: event.minutes, exam: Boolean(event.exam), writingSeconds: 0 };
+      } else if (event.type === 'session-end') {
+        if (!open) {
+          results.errors.push(`Epoch ${i}: Timed session ends without having started.`);
+          return;
+        }
+        open.end = i;
+        results.timedSessions.push(open);
+        open = null;
+      }
+    });
+  });
+  if (open) {
+    results.warnings.push(`Timed session starting at epoch ${open.start} was never closed.`);
+    results.timedSessions.push(open);
+  }
+}
+
+// Epochs declare the squarings per second their producer measured at startup.
+// One that claims to have finished much faster than that is suspicious.
+const DECLARED_RATE_TOLERANCE = 3;
+
+function checkEpochSpeed(epoch, index, results) {
+  if (!(epoch.declaredRate > 0) || !(epoch.epochDuration > 0)) return;
+  const rate = epoch.iterations / epoch.epochDuration;
+  if (rate > epoch.declaredRate * DECLARED_RATE_TOLERANCE) {
+    results.warnings.push(`Epoch ${index}: Reported ${(rate / epoch.declaredRate).toFixed(1)}x faster than its producer's declared speed.`);
+  }
+}
+
+// Compares the time each epoch's VDF needs at its declared speed with what the
+// system clock recorded. One odd epoch is noise; a run of them is either a clock
+// set back (less time passed than the squarings take) or a throttled machine.
+const CLOCK_DRIFT_TOLERANCE = 2;
+const CLOCK_DRIFT_MIN_EPOCHS = 3;
+
+function checkClockDrift(epochs, results) {
+  const drift = { epochs: 0, vdfSeconds: 0, clockSeconds: 0, runs: [] };
+  let run = null;
+  const closeRun = () => {
+    if (run && run.end - run.start + 1 >= CLOCK_DRIFT_MIN_EPOCHS) drift.runs.push(run);
+    run = null;
+  };
+  epochs.forEach((epoch, i) => {
+    if (i === 0 || !(epoch.declaredRate > 0) || !(epoch.iterations > 0)) return closeRun();
+    const vdfSeconds = epoch.iterations / epoch.declaredRate;
+    const interval = (new Date(epoch.timestamp) - new Date(epochs[i - 1].timestamp)) / 1000;
+    drift.epochs++;
+    drift.vdfSeconds += vdfSeconds;
+    drift.clockSeconds += epoch.epochDuration || 0;
+    let kind = null;
+    if (interval < vdfSeconds / CLOCK_DRIFT_TOLERANCE) kind = 'clock-behind';
+    else if (epoch.epochDuration > vdfSeconds * CLOCK_DRIFT_TOLERANCE) kind = 'throttled';
+    if (run && run.kind !== kind) closeRun();
+    if (kind) run = run || { kind, start: i, end: i };
+    if (run) run.end = i;
+  });
+  closeRun();
+  drift.runs.forEach(({ kind, start, end }) => {
+    results.warnings.push(kind === 'clock-behind'
+      ? `Epochs ${start}-${end}: System clock recorded less time than their VDF proofs take. The clock may have been changed.`
+      : `Epochs ${start}-${end}: Took over ${CLOCK_DRIFT_TOLERANCE}x longer than their VDF proofs need. The machine was heavily throttled.`);
+  });
+  results.clockDrift = drift;
+}
+
+// Typing cadence: a histogram of the intervals between keystrokes in an epoch,
+// counted into buckets with these upper bounds (the last one is open-ended).
+// Only the hash is covered by the epoch hash, so the histogram itself can be
+// stripped from an export without breaking verification.
+const CADENCE_BUCKETS_MS = [50, 100, 200, 400, 800, 1600];
+
+function cadenceBucket(intervalMs) {
+  const index = CADENCE_BUCKETS_MS.findIndex(bound => intervalMs < bound);
+  return index === -1 ? CADENCE_BUCKETS_MS.length : index;
+}
+
+async function checkEpochCadence(epoch, index, results) {
+  if (!epoch.cadence) return;
+  if (await sha256(canonicalJson(epoch.cadence)) !== epoch.cadenceHash) {
+    results.errors.push(`Epoch ${index}: Typing cadence does not match its committed hash.`);
+    results.valid = false;
+  }
+}
+
+function checkEpochSha3(epoch, previous, index, results) {
+  if (epoch.hashSha3) {
+    if (epoch.hashSha3 !== epochHashSha3(epoch, previous)) {
+      results.errors.push(`Epoch ${index}: SHA3 hash mismatch.`);
+      results.valid = false;
+    }
+  } else if (previous.hashSha3) {
+    results.warnings.push(`Epoch ${index}: Dual hashing stops here; later epochs rely on SHA-256 alone.`);
+  }
+}
+
+// A resumed epoch must point at the exact epoch the previous session ended on.
+function checkSessionLinkage(epoch, previous, index, results) {
+  if (epoch.epochNumber !== index) {
+    results.errors.push(`Epoch ${index}: Out-of-sequence epoch number ${epoch.epochNumber}.`);
+    results.valid = false;
+  }
+  if (!epoch.resumedFrom) return;
+  results.sessions++;
+  if (epoch.resumedFrom.epochNumber !== previous.epochNumber || epoch.resumedFrom.hash !== previous.hash) {
+    results.errors.push(`Epoch ${index}: Session restart does not link to the previous session.`);
+    results.valid = false;
+  }
+  if (previous.timestamp && new Date(epoch.timestamp) < new Date(previous.timestamp)) {
+    results.errors.push(`Epoch ${index}: Session restart predates the previous session.`);
+    results.valid = false;
+  }
+}
+
+// Describes what verifyDocument would check for these options without running
+// any check, so users can see what each option covers before paying for it.
+function explainVerification(documentData, options = {}, iterationsPerSecond = null) {
+  const epochs = documentData.proofChain;
+  const from = Math.max(1, options.range?.from ?? 1);
+  const to = Math.min(epochs.length - 1, options.range?.to ?? epochs.length - 1);
+  const selected = epochs.slice(from, to + 1);
+  const iterations = selected.reduce((sum, epoch) => sum + (epoch.iterations || 0), 0);
+  const legacy = selected.filter(epoch => epoch.hashVersion === undefined).length;
+  const custody = (documentData.custody || []).length;
+  const lines = [
+    documentData.provenance?.vdfBackend === CLASS_GROUP_BACKEND
+      ? 'Group: class group of the built-in discriminant, checked against its fingerprint'
+      : `Modulus: ${documentData.provenance?.modulusId || DEFAULT_MODULUS_ID}, checked against the registry and for known weaknesses`,
+    `Epochs: ${selected.length} of ${epochs.length - 1}${options.range ? ` (range ${from}-${to})` : ''}`,
+    `  each: link to previous hash, epoch hash (${selected.length - legacy} canonical, ${legacy} legacy encoding),`,
+    `        difficulty changes, session links, undo/redo events, declared speed, typing cadence, SHA3 chain, then its VDF proof`,
+    `VDF proofs: ${selected.length}, attesting ${iterations.toLocaleString()} sequential squarings` +
+      (iterationsPerSecond ? ` (about ${(iterations / iterationsPerSecond / 60).toFixed(1)} min of work on this machine)` : ''),
+    `Timed sessions: markers across the whole chain`,
+    `Clock drift: VDF time against system clock across the whole chain`,
+    `Signature: ${documentData.metadata.signature ? 'ECDSA P-384 over the document hash' : 'none, verification will fail'}`,
+    `Custody transfers: ${custody}${custody > 0 ? ', each sender signature and countersignature' : ''}`,
+    `Warnings: ${options.strict ? 'fail verification (strict mode)' : 'reported without failing'}`
+  ];
+  if (options.stopOnError) lines.push('Stops at the first error; VDF proofs of a failing epoch are skipped');
+  if (options.maxFindings > 0) lines.push(`Stops after ${options.maxFindings} findings`);
+  if (options.timeBudget > 0) lines.push(`Spends about ${options.timeBudget}s on VDF proofs and samples the rest evenly`);
+  return lines.join('\n');
+}
+
+// Walks through the Wesolowski check for one epoch with the actual numbers, so a
+// reviewer can follow y = pi^l * x^r (mod N) instead of taking a checkmark on trust
+// Uses the VDF setup recorded in the provenance of the document the epoch belongs to
+async function explainProofMath(epoch, provenance) {
+  await init();
+  if ((provenance?.vdfBackend || RSA_BACKEND) !== RSA_BACKEND) throw new Error('Explain Proof only covers RSA documents.');
+  const { computer } = computerForProvenance(provenance);
+  const vdfProof = new VDFProof(epoch.vdfProof.y, epoch.vdfProof.pi, epoch.vdfProof.l, epoch.vdfProof.r, BigInt(epoch.iterations));
+  const e = JSON.parse(computer.explain_proof(epoch.previousHash, vdfProof));
+  const hex = (value) => `0x${value.length > 32 ? `${value.slice(0, 16)}…${value.slice(-16)}` : value}`;
+  const mark = (ok) => ok ? '✓' : '✗';
+  return [
+    `Epoch ${epoch.epochNumber}: t = ${e.iterations.toLocaleString()} sequential squarings`,
+    `N: ${e.modulusBits}-bit modulus, fingerprint ${e.modulusFingerprint.substring(0, 16)}...`,
+    '',
+    `1. Input: previous epoch hash ${e.input.substring(0, 16)}...`,
+    `   x = hash_to_group(input) = ${hex(e.x)}`,
+    `2. Claimed output y = x^(2^t) mod N = ${hex(e.y)}`,
+    '3. Fiat-Shamir: l is the first 128-bit prime drawn from',
+    '   SHA-256("VDF_FIAT_SHAMIR_v1" || x || y || t || N || attempt)',
+    `   l in proof  = ${hex(e.l)}  prime ${mark(e.lIsPrime)}`,
+    `   l recomputed = ${hex(e.expectedL)}  ${mark(e.lMatchesFiatShamir)}`,
+    `4. r = 2^t mod l = ${hex(e.r)}  ${mark(e.rMatches)}`,
+    `5. Proof π = x^floor(2^t / l) mod N = ${hex(e.pi)}`,
+    '6. Check y ≟ π^l · x^r (mod N)',
+    `   π^l mod N     = ${hex(e.piL)}`,
+    `   x^r mod N     = ${hex(e.xR)}`,
+    `   π^l · x^r mod N = ${hex(e.product)}`,
+    `   y             = ${hex(e.y)}`,
+    '',
+    e.holds && e.lMatchesFiatShamir && e.lIsPrime
+      ? 'The equation holds: y is x squared t times, and computing it took t sequential steps.'
+      : 'The proof does not verify.'
+  ].join('\n');
+}
+
+// The last verification result, keyed by a digest of everything verifyDocument
+// reads. Any change to the chain, signature, modulus registry or options misses.
+let verificationCache = null;
+
+async function verificationCacheKey(documentData, options) {
+  return await sha256(canonicalJson({
+    proofChain: documentData.proofChain,
+    vdfBackend: documentData.provenance?.vdfBackend,
+    modulusId: documentData.provenance?.modulusId,
+    modulusFingerprint: documentData.provenance?.modulusFingerprint,
+    discriminantFingerprint: documentData.provenance?.discriminantFingerprint,
+    seeded: documentData.provenance?.seeded,
+    signature: [documentData.metadata.publicKey, documentData.metadata.signature, documentData.metadata.documentHash,
+      documentData.metadata.signatureSha3, documentData.metadata.documentHashSha3],
+    custody: documentData.custody,
+    moduli: localStorage.getItem(LS_MODULI),
+    options: [options.strict, options.stopOnError, options.maxFindings, options.range, options.timeBudget]
+  }));
+}
+
+// Options: strict - treat every warning as a failure
+//          timeBudget - seconds to spend on VDF proofs; the rest are sampled
+async function verifyDocument(documentData, updateProgress, options = {}) {
+  const results = { valid: true, errors: [], warnings: [], verifiedEpochs: 0, totalEpochs: 0, sessions: 1, retractions: 0, signatureValid: false };
+  const cacheKey = await verificationCacheKey(documentData, options).catch(() => null);
+  if (cacheKey && verificationCache?.key === cacheKey) {
+    if (updateProgress) updateProgress(100, 'Verification complete (cached).');
+    return { ...structuredClone(verificationCache.results), cached: true };
+  }
+  try {
+    // 1. Verify VDF Chain
+    await init();
+    const { profile, computer } = computerForProvenance(documentData.provenance);
+    if (profile) {
+      if (profile.trust !== 'trusted') {
+        results.warnings.push(`Document uses the ${profile.trust} modulus "${profile.name}".`);
+      }
+      computer.modulus_weaknesses().forEach(weakness => {
+        results.errors.push(`Modulus: ${weakness}.`);
+        results.valid = false;
+      });
+      const documentModulus = documentData.provenance?.modulusFingerprint;
+      if (documentModulus && documentModulus !== computer.modulus_fingerprint()) {
+        results.errors.push(`Modulus: Fingerprint ${documentModulus.substring(0, 16)}... does not match registry entry "${profile.id}".`);
+        results.valid = false;
+      }
+    } else {
+      const documentDiscriminant = documentData.provenance?.discriminantFingerprint;
+      if (documentDiscriminant && documentDiscriminant !== computer.discriminant_fingerprint()) {
+        results.errors.push(`Discriminant: Fingerprint ${documentDiscriminant.substring(0, 16)}... does not match the built-in class group.`);
+        results.valid = false;
+      }
+    }
+    const epochs = documentData.proofChain;
+    // A range verifies only epochs from..to, plus the link to the epoch before them
+    const from = Math.max(1, options.range?.from ?? 1);
+    const to = Math.min(epochs.length - 1, options.range?.to ?? epochs.length - 1);
+    if (options.range) results.range = { from, to };
+    results.totalEpochs = Math.max(0, to - from + 1);
+    if (options.range && from > to) {
+      results.errors.push(`Range ${options.range.from}-${options.range.to} selects none of the ${epochs.length - 1} epochs.`);
+      results.valid = false;
+    }
+    // Findings are reported per epoch as they are found so the UI can show them
+    // before the whole chain is done.
+    let reported = { errors: results.errors.length, warnings: results.warnings.length };
+    const reportDetail = (epochNumber) => {
+      const detail = {
+        epochNumber,
+        errors: results.errors.slice(reported.errors),
+        warnings: results.warnings.slice(reported.warnings)
+      };
+      reported = { errors: results.errors.length, warnings: results.warnings.length };
+      if (options.onDetail) options.onDetail(detail);
+    };
+    // With stopOnError or maxFindings set, a tampered file stops verifying early
+    // and the partial results are marked truncated.
+    const limitReached = () =>
+      (options.stopOnError && results.errors.length > 0) ||
+      (options.maxFindings > 0 && results.errors.length + results.warnings.length >= options.maxFindings);
+    reportDetail(null); // Document-level findings
+    // With a time budget, the measured cost per proof decides how many of the
+    // remaining proofs still fit; those are checked at an even stride, always
+    // including the last. Every cheap check still runs on every epoch.
+    const budgetMs = options.timeBudget > 0 ? options.timeBudget * 1000 : null;
+    const budgetStart = performance.now();
+    let proofMs = 0, proofsChecked = 0, stride = 1, skippedProofs = 0;
+    let checked = 0;
+    for (let i = from; i <= to; i++) {
+      if (i > from) reportDetail(i - 1);
+      if (limitReached()) { results.truncated = true; break; }
+      checked = i;
+      const epoch = epochs[i];
+      if (updateProgress) updateProgress(((i - from + 1) / results.totalEpochs) * 90, `Verifying epoch ${i}...`);
+      if (epoch.previousHash !== epochs[i - 1].hash) { results.errors.push(`Epoch ${i}: Broken chain.`); results.valid = false; continue; }
+      // Cheap checks run before the VDF proof, which is skipped once an early stop is due
+      let expectedHash;
+      try { expectedHash = await epochHash(epoch); } catch (e) { expectedHash = null; }
+      if (epoch.hash !== expectedHash) { results.errors.push(`Epoch ${i}: Hash mismatch.`); results.valid = false; }
+      checkDifficultyChange(epoch, epochs[i - 1], i, results);
+      checkSessionLinkage(epoch, epochs[i - 1], i, results);
+      checkEpochEvents(epoch, i, results);
+      checkEpochSpeed(epoch, i, results);
+      await checkEpochCadence(epoch, i, results);
+      checkEpochSha3(epoch, epochs[i - 1], i, results);
+      if (limitReached()) { results.truncated = true; break; }
+      if (budgetMs !== null && (i - from) % stride !== 0 && i !== to) { skippedProofs++; continue; }
+      const proofStart = performance.now();
+      const vdfProof = new VDFProof(epoch.vdfProof.y, epoch.vdfProof.pi, epoch.vdfProof.l, epoch.vdfProof.r, BigInt(epoch.iterations));
+      if (!(await computer.verify_proof(epoch.previousHash, vdfProof))) { results.errors.push(`Epoch ${i}: Invalid VDF proof.`); results.valid = false; } else { results.verifiedEpochs++; }
+      proofMs += performance.now() - proofStart;
+      proofsChecked++;
+      if (budgetMs !== null) {
+        const affordable = Math.floor((budgetMs - (performance.now() - budgetStart)) / (proofMs / proofsChecked));
+        stride = affordable >= to - i ? 1 : affordable > 0 ? Math.ceil((to - i) / affordable) : Infinity;
+      }
+    }
+    if (budgetMs !== null) {
+      results.coverage = { budgetSeconds: options.timeBudget, checkedProofs: proofsChecked, skippedProofs };
+      if (skippedProofs > 0) results.warnings.push(`${skippedProofs} VDF proofs were sampled out to fit the ${options.timeBudget}s time budget: sampled, not verified.`);
+    }
+    if (checked > 0) reportDetail(checked);
+    checkSeeded(documentData, results);
+    checkTimedSessions(epochs, results);
+    checkClockDrift(epochs, results);
+    checkContentEntropy(epochs, results);
+
+    // 2. Verify Signature
+    if (updateProgress) updateProgress(95, 'Verifying signature...');
+    const { publicKey, signature, documentHash } = documentData.metadata;
+    if (publicKey && signature && documentHash) {
+      const pubKey = await cryptoHelpers.importKey(publicKey, 'ECDSA', ['verify']);
+      results.signatureValid = await cryptoHelpers.verify(documentHash, signature, pubKey);
+      if (!results.signatureValid) {
+        results.errors.push("Author signature is invalid.");
+      }
+      const { documentHashSha3, signatureSha3 } = documentData.metadata;
+      if (signatureSha3 && !(await cryptoHelpers.verify(documentHashSha3, signatureSha3, pubKey))) {
+        results.signatureValid = false;
+        results.errors.push("Author signature over the SHA3 document hash is invalid.");
+      }
+    } else {
+      results.errors.push("Document is not signed.");
+    }
+    await checkCustody(documentData, results);
+    reportDetail(null);
+
+    results.truncated = Boolean(results.truncated);
+    results.strict = Boolean(options.strict);
+    if (results.strict) {
+      results.errors.push(...results.warnings.map(warning => `Strict: ${warning}`));
+      results.warnings = [];
+    }
+
+    results.valid = results.errors.length === 0;
+    if (updateProgress) updateProgress(100, 'Verification complete.');
+    // Sampled results depend on timing, so they are never reused
+    if (cacheKey && !results.coverage?.skippedProofs) verificationCache = { key: cacheKey, results: structuredClone(results) };
+  } catch (error) {
+    results.valid = false;
+    results.errors.push(error.message);
+  }
+  return results;
+}
+
+// Seeded documents run on a simulated clock, so their timestamps say nothing
+// about when they were written. A warning, and so an error in strict mode.
+function checkSeeded(documentData, results) {
+  const seededEpochs = documentData.proofChain.filter(epoch => epoch.seeded).length;
+  if (documentData.provenance?.seeded === undefined && seededEpochs === 0) return;
+  results.seeded = true;
+  results.warnings.push(`Seeded demo document (${seededEpochs} simulated epochs): its timestamps are not evidence of when it was written.`);
+}
+
+// verifyDocument checks the signature over metadata.documentHash; this checks
+// that the document's content actually hashes to it.
+async function contentHashErrors(documentData) {
+  const errors = [];
+  if (await computeDocumentHash(documentData).catch(() => null) !== documentData.metadata.documentHash) {
+    errors.push('Document content does not match its signed hash.');
+  }
+  if (documentData.metadata.documentHashSha3 && computeDocumentHashSha3(documentData) !== documentData.metadata.documentHashSha3) {
+    errors.push('Document content does not match its signed SHA3 hash.');
+  }
+  return errors;
+}
+
+// Checks a received attestation package: the document itself is verified from
+// scratch, then every bundled artifact is checked against that document.
+async function verifyAttestationPackage(pkg, updateProgress, options = {}) {
+  const documentData = pkg.document;
+  const results = await verifyDocument(documentData, updateProgress, options);
+  const fail = (message) => { results.errors.push(`Package: ${message}`); results.valid = false; };
+
+  (await contentHashErrors(documentData)).forEach(fail);
+  const documentText = (documentData.content.delta.ops || [])
+    .map(op => (typeof op.insert === 'string' ? op.insert : ''))
+    .join('');
+  if (pkg.text !== documentText) fail('Bundled text does not match the document.');
+  if (JSON.stringify(pkg.publicKey) !== JSON.stringify(documentData.metadata.publicKey)) {
+    fail('Bundled public key does not match the document signer.');
+  }
+  const report = pkg.verification || {};
+  if (report.valid !== results.valid || report.verifiedEpochs !== results.verifiedEpochs) {
+    fail('Bundled verification report disagrees with independent verification.');
+  }
+  return results;
+}
+
+// --- Chain of Custody ---
+// A transfer record is signed by the sender and names the recipient's public
+// key; the recipient countersigns it when they first open the document. Records
+// link to their predecessor, so the custody chain cannot be reordered.
+function sameKey(a, b) {
+  return Boolean(a && b) && a.x === b.x && a.y === b.y && a.crv === b.crv;
+}
+
+async function custodyRecordHash(record) {
+  const { from, to, headHash, documentHash, at, previous } = record;
+  return await sha256(canonicalJson({ from, to, headHash, documentHash, at, previous }));
+}
+
+async function createTransferRecord(docData, recipientKey, signingKeys) {
+  const custody = docData.custody || [];
+  const record = {
+    from: await cryptoHelpers.exportKey(signingKeys.publicKey),
+    to: recipientKey,
+    headHash: docData.proofChain[docData.proofChain.length - 1].hash,
+    documentHash: docData.metadata.documentHash,
+    at: new Date().toISOString(),
+    previous: custody.length > 0 ? await custodyRecordHash(custody[custody.length - 1]) : null
+  };
+  record.signature = await cryptoHelpers.sign(await custodyRecordHash(record), signingKeys.privateKey);
+  return record;
+}
+
+async function countersignTransfer(record, signingKeys) {
+  const acceptedAt = new Date().toISOString();
+  const digest = await sha256(canonicalJson({ record: await custodyRecordHash(record), acceptedAt }));
+  return { ...record, countersignature: { acceptedAt, signature: await cryptoHelpers.sign(digest, signingKeys.privateKey) } };
+}
+
+async function checkCustody(documentData, results) {
+  const custody = documentData.custody || [];
+  results.custody = custody.map(record => ({ from: record.from, to: record.to, at: record.at, accepted: Boolean(record.countersignature) }));
+  // Transfers hand on this signed version: the first comes from its signer, and
+  // each names this document hash and a head in its chain
+  const heads = new Set(documentData.proofChain.map(epoch => epoch.hash));
+  if (custody.length > 0 && !sameKey(custody[0].from, documentData.metadata.publicKey)) {
+    results.errors.push('Custody 1: Sender is not the signer of the document.');
+  }
+  let previous = null;
+  for (let i = 0; i < custody.length; i++) {
+    const record = custody[i];
+    const hash = await custodyRecordHash(record);
+    const fromKey = await cryptoHelpers.importKey(record.from, 'ECDSA', ['verify']);
+    if (!(await cryptoHelpers.verify(hash, record.signature, fromKey))) {
+      results.errors.push(`Custody ${i + 1}: Sender signature is invalid.`);
+    }
+    if (record.previous !== (previous ? await custodyRecordHash(previous) : null) || (previous && !sameKey(record.from, previous.to))) {
+      results.errors.push(`Custody ${i + 1}: Transfer does not follow the previous holder.`);
+    }
+    if (record.documentHash !== documentData.metadata.documentHash) {
+      results.errors.push(`Custody ${i + 1}: Transfer names a different version of the document.`);
+    }
+    if (!heads.has(record.headHash)) {
+      results.errors.push(`Custody ${i + 1}: Transferred head is not in the proof chain.`);
+    }
+    if (record.countersignature) {
+      const digest = await sha256(canonicalJson({ record: hash, acceptedAt: record.countersignature.acceptedAt }));
+      const toKey = await cryptoHelpers.importKey(record.to, 'ECDSA', ['verify']);
+      if (!(await cryptoHelpers.verify(digest, record.countersignature.signature, toKey))) {
+        results.errors.push(`Custody ${i + 1}: Recipient countersignature is invalid.`);
+      }
+    } else if (i < custody.length - 1) {
+      results.warnings.push(`Custody ${i + 1}: Passed on without being accepted by its recipient.`);
+    }
+    previous = record;
+  }
+}
+
+// --- Attested Quotes ---
+// Copying from the editor can carry the epochs that typed the selection, so the
+// document receiving the paste can check when and how that text was written.
+const QUOTE_FORMAT = 'bitquill-quote';
+const QUOTE_MIME = 'application/x-bitquill-quote+json';
+
+// The quote carries in full only the epochs that typed the selection. Every
+// epoch from the first of them to the head comes as a link (hash and VDF proof,
+// no deltas), so later writing does not travel with the quote. When the head
+// was saved, the author's signed root feed entry for it comes too.
+const QUOTE_LINK_FIELDS = ['epochNumber', 'previousHash', 'hash', 'vdfProof', 'iterations'];
+
+function buildQuote(chain, text, index, length, title, provenance, signedHead = null, publicKey = null) {
+  const owners = computeBlame(chain, text);
+  if (!owners) return null;
+  // The document's final newline comes from genesis, which has no epoch to attest it
+  while (length > 0 && owners[index + length - 1] === 0) length--;
+  const span = owners.slice(index, index + length);
+  if (span.length === 0 || span.includes(0)) return null;
+  const runs = [];
+  span.forEach(epochNumber => {
+    if (runs.length > 0 && runs[runs.length - 1].epochNumber === epochNumber) runs[runs.length - 1].length++;
+    else runs.push({ epochNumber, length: 1 });
+  });
+  const written = new Set(span);
+  const first = Math.min(...written);
+  const headHash = chain[chain.length - 1].hash;
+  return {
+    format: QUOTE_FORMAT,
+    version: 3,
+    text: text.substring(index, index + length),
+    title,
+    copiedAt: new Date().toISOString(),
+    vdfBackend: provenance?.vdfBackend || RSA_BACKEND,
+    modulusId: provenance?.modulusId,
+    headHash,
+    ...(signedHead && publicKey ? { head: signedHead, publicKey } : {}),
+    runs,
+    epochs: chain.filter(epoch => written.has(epoch.epochNumber)),
+    links: chain.filter(epoch => epoch.epochNumber >= first)
+      .map(epoch => Object.fromEntries(QUOTE_LINK_FIELDS.map(field => [field, epoch[field]])))
+  };
+}
+
+// Replays one epoch over a document whose earlier text is unknown (null), and
+// returns the characters it leaves behind.
+function replayEpochAlone(epoch) {
+  const chars = [];
+  const reach = index => { while (chars.length < index) chars.push(null); };
+  (epoch.deltas || []).forEach(delta => {
+    let index = 0;
+    ((delta && delta.ops) || []).forEach(op => {
+      if (op.retain !== undefined) {
+        index += op.retain;
+      } else if (op.insert !== undefined) {
+        reach(index);
+        const inserted = typeof op.insert === 'string' ? [...op.insert] : [null];
+        chars.splice(index, 0, ...inserted);
+        index += inserted.length;
+      } else if (op.delete !== undefined) {
+        reach(index + op.delete);
+        chars.splice(index, op.delete);
+      }
+    });
+  });
+  return chars.map(char => char ?? '\0').join('');
+}
+
+// The VDF setup must be a trusted, sound one, or its proofs say nothing. Every
+// link must prove and follow the one before up to the quoted head, and every
+// full epoch must hash to its link. Links carry no deltas, so their hashes are
+// taken on trust; forging them still costs the VDF work they prove. A carried
+// head entry must be signed over the quoted head. Each run of the quote must
+// appear whole in what the epoch that typed it wrote.
+async function verifyQuote(quote) {
+  const errors = [];
+  if (!quote.text || !quote.text.trim()) errors.push('The quote is empty.');
+  if (!Array.isArray(quote.epochs) || quote.epochs.length === 0) errors.push('The quote carries no epochs.');
+  if (!Array.isArray(quote.links) || quote.links.length === 0) errors.push('The quote carries no links to its head.');
+  if (!Array.isArray(quote.runs) || quote.runs.reduce((sum, run) => sum + run.length, 0) !== [...(quote.text || '')].length) {
+    errors.push('The quote does not say which epochs typed it.');
+  }
+  if (errors.length > 0) return { valid: false, errors, epochs: [], signed: false };
+  await init();
+  let computer;
+  try {
+    const setup = computerForProvenance(quote);
+    computer = setup.computer;
+    if (setup.profile && setup.profile.trust !== 'trusted') errors.push(`Modulus: The ${setup.profile.trust} modulus "${setup.profile.name}" cannot attest quotes.`);
+    if (setup.profile) computer.modulus_weaknesses().forEach(weakness => errors.push(`Modulus: ${weakness}.`));
+  } catch (error) {
+    errors.push(error.message);
+  }
+  if (errors.length > 0) return { valid: false, errors, epochs: [], signed: false };
+
+  const links = new Map();
+  for (const [i, link] of quote.links.entries()) {
+    if (i > 0 && (link.epochNumber !== quote.links[i - 1].epochNumber + 1 || link.previousHash !== quote.links[i - 1].hash)) {
+      errors.push(`Epoch ${link.epochNumber}: Does not follow epoch ${quote.links[i - 1].epochNumber}.`);
+    }
+    const vdfProof = new VDFProof(link.vdfProof.y, link.vdfProof.pi, link.vdfProof.l, link.vdfProof.r, BigInt(link.iterations));
+    if (!(await computer.verify_proof(link.previousHash, vdfProof))) errors.push(`Epoch ${link.epochNumber}: Invalid VDF proof.`);
+    links.set(link.epochNumber, link);
+  }
+  if (quote.links[quote.links.length - 1].hash !== quote.headHash) errors.push('The links do not end at the quoted head.');
+  for (const epoch of quote.epochs) {
+    const link = links.get(epoch.epochNumber);
+    if (!link || epoch.hash !== link.hash || epoch.hash !== await epochHash(epoch).catch(() => null)) errors.push(`Epoch ${epoch.epochNumber}: Hash mismatch.`);
+  }
+
+  let signed = false;
+  if (quote.head) {
+    const { signature, ...entry } = quote.head;
+    const publicKey = await cryptoHelpers.importKey(quote.publicKey, 'ECDSA', ['verify']).catch(() => null);
+    if (entry.headHash !== quote.headHash) errors.push('The signed head is not the quoted head.');
+    else if (!publicKey || !signature || !(await cryptoHelpers.verify(canonicalJson(entry), signature, publicKey))) errors.push('The head signature is invalid.');
+    else signed = true;
+  }
+
+  const written = new Map(quote.epochs.map(epoch => [epoch.epochNumber, replayEpochAlone(epoch)]));
+  const chars = [...quote.text];
+  let at = 0;
+  quote.runs.forEach(({ epochNumber, length }) => {
+    const run = chars.slice(at, at + length).join('');
+    at += length;
+    if (!written.has(epochNumber) || !written.get(epochNumber).includes(run)) {
+      errors.push(`Epoch ${epochNumber}: Did not write "${run.length > 20 ? run.substring(0, 20) + '...' : run}" as quoted.`);
+    }
+  });
+  const epochs = [...new Set(quote.runs.map(run => run.epochNumber))].sort((a, b) => a - b);
+  return { valid: errors.length === 0, errors, epochs, signed };
+}
+
+// Declare state variables and helper functions in the global scope
+let userKeys = null;
 let appState = {
   currentDocumentKey: null,
   isDirty: false,
-  loadedMetadata: null // Add this line to store original metadata
+  loadedMetadata: null, // Add this line to store original metadata
+  loadedProvenance: null, // VDF setup the open document was loaded with
+  custody: [], // Transfer records of the open document
+  bookmarks: [] // { name, index, epochNumber }: editor positions, optionally tied to the epoch that wrote them
 };
 let proofChain = [];
 let quill;
 
+// Identifies the build and VDF setup that produced a save, so discrepancies found
+// during verification can be traced back to it. Filled in once the VDF is ready.
+// The browser environment identifies the author's machine, so it is opt-in.
+let vdfProvenance = null;
+
+function buildProvenance() {
+  return {
+    appVersion: __APP_VERSION__,
+    buildCommit: __BUILD_COMMIT__,
+    ...vdfProvenance,
+    ...(seed !== null ? { seeded: seed } : {}),
+    ...(settings.recordEnvironment
+      ? { environment: { userAgent: navigator.userAgent, platform: navigator.platform, language: navigator.language } }
+      : {})
+  };
+}
+
+function describeProvenance(provenance) {
+  if (!provenance) return 'Unknown (saved before provenance was recorded)';
+  return `BitQuill ${provenance.appVersion} (${provenance.buildCommit}), vdf-wasm ${provenance.vdfVersion}, ${provenance.vdfBackend}`;
+}
+
 async function buildDocumentData() {
   const title = document.getElementById('document-title').value || "Untitled Document";
   const docData = {
-    title, version: "2.1-crypto", timestamp: new Date().toISOString(),
+    title, version: DOCUMENT_VERSION, hashVersion: HASH_VERSION, timestamp: now().toISOString(),
     content: { html: quill.root.innerHTML, delta: quill.getContents() },
     proofChain,
+    provenance: buildProvenance(),
+    ...(appState.custody.length > 0 ? { custody: appState.custody } : {}),
+    ...(appState.bookmarks.length > 0 ? { bookmarks: appState.bookmarks } : {}),
     metadata: {
       epochCount: proofChain.length,
       genesisHash: proofChain[0]?.hash,
@@ -887,38 +2528,104 @@ async function buildDocumentData() {
       documentHash: null, publicKey: null, signature: null
     }
   };
-  const dataToHash = { ...docData, metadata: { ...docData.metadata, documentHash: null, signature: null } };
-  docData.metadata.documentHash = await sha256(JSON.stringify(dataToHash));
+  const latest = proofChain[proofChain.length - 1];
+  if (latest?.hashSha3) docData.metadata.latestHashSha3 = latest.hashSha3;
+  // The SHA3 hash is computed first so the SHA-256 hash covers it too
+  if (settings.dualHash) docData.metadata.documentHashSha3 = computeDocumentHashSha3(docData);
+  docData.metadata.documentHash = await computeDocumentHash(docData);
   return docData;
 }
 
+// Version 2.2 documents leave out of their hash what scrubForExport may remove:
+// cadence histograms, which epochs commit to through cadenceHash, and the
+// producing environment. Version 2.1 documents hash everything.
+const DOCUMENT_VERSION = '2.2-crypto';
+
+function documentHashContent(docData) {
+  if (docData.version !== DOCUMENT_VERSION) return docData;
+  const { environment, ...provenance } = docData.provenance || {};
+  return {
+    ...docData,
+    provenance: docData.provenance ? provenance : undefined,
+    proofChain: docData.proofChain.map(({ cadence, ...epoch }) => epoch)
+  };
+}
+
+// The hash the author signs; the signing fields are blank when it is computed.
+async function computeDocumentHash(docData) {
+  // Custody records are appended after signing and carry their own signatures
+  const dataToHash = { ...documentHashContent(docData), custody: undefined, metadata: { ...docData.metadata, documentHash: null, publicKey: null, signature: null, signatureSha3: undefined } };
+  return await sha256(hashEncoding(docData.hashVersion)(dataToHash));
+}
+
+function computeDocumentHashSha3(docData) {
+  const metadata = { ...docData.metadata, documentHash: null, publicKey: null, signature: null, documentHashSha3: null, signatureSha3: undefined };
+  return sha3_256_hex(canonicalJson({ ...documentHashContent(docData), custody: undefined, metadata }));
+}
+
 
 // --- Main App Logic ---
+const VDF_MAX_RETRIES = 5;
+
 async function run() {
   await init();
-
-  const computer = new VDFComputer();
-  const targetSeconds = 10;
-  let calibratedIterations = 100000; // Fallback
-  try {
-    console.log('Starting VDF calibration...');
-    const baselineIterations = 10000;
-    const baselineStart = performance.now();
-    await computer.compute_proof("calibration", BigInt(baselineIterations), null);
-    const baselineTime = (performance.now() - baselineStart) / 1000;
-    const iterationsPerSecond = baselineIterations / baselineTime;
-    calibratedIterations = Math.floor(iterationsPerSecond * targetSeconds);
-    console.log(`Calibrated to ${calibratedIterations} iterations for ${targetSeconds}s`);
-  } catch (e) {
-    console.error("VDF calibration failed, using fallback.", e);
-    showToast("VDF calibration failed, using default timing.", "error");
+  set_log_level(settings.logLevel);
+
+  let calibratedIterations, calibration, difficultyReason;
+  // The VDF rejects iteration counts outside these, whatever the calibration measured
+  const MIN_ITERATIONS = 1000;
+  const MAX_ITERATIONS = 100000000;
+  const iterationsFor = (seconds) => Math.min(Math.max(Math.floor(calibration.iterationsPerSecond * seconds), MIN_ITERATIONS), MAX_ITERATIONS);
+  // Switches the VDF that new epochs run in. Each group squares at its own speed,
+  // so the iterations per epoch are measured again.
+  function useBackend(backend) {
+    vdfProvenance = vdfProvenanceFor(backend);
+    const { computer } = computerForProvenance(vdfProvenance);
+    const { baselineIterations, fallbackIterations, seededIterations } = VDF_BACKENDS[backend];
+    calibration = null;
+    if (seed !== null) {
+      calibratedIterations = seededIterations;
+      difficultyReason = 'seeded';
+      return;
+    }
+    calibratedIterations = fallbackIterations;
+    difficultyReason = 'fallback';
+    try {
+      console.log(`Starting VDF calibration (${backend})...`);
+      const baselineStart = performance.now();
+      computer.compute_proof("calibration", BigInt(baselineIterations), null);
+      const baselineTime = (performance.now() - baselineStart) / 1000;
+      const iterationsPerSecond = baselineIterations / baselineTime;
+      calibration = { baselineIterations, baselineSeconds: baselineTime, iterationsPerSecond };
+      calibratedIterations = iterationsFor(settings.epochSeconds);
+      difficultyReason = 'calibration';
+      console.log(`Calibrated to ${calibratedIterations} iterations for ${settings.epochSeconds}s`);
+    } catch (e) {
+      console.error("VDF calibration failed, using fallback.", e);
+      showToast("VDF calibration failed, using default timing.", "error");
+    }
   }
-
+  useBackend(VDF_BACKENDS[settings.vdfBackend] ? settings.vdfBackend : RSA_BACKEND);
+
+  let runningIterations = calibratedIterations; // Iterations of the VDF currently in the worker
+  let commitRequested = false; // Manual and paragraph commit policies: bind pending edits at the next epoch
+  let editedLine = null; // Paragraph policy: the line of the last edit
+  let sentenceIdleTimer = null;
+  let anchorRequested = false; // Re-anchor: link a loaded document to this session at the next epoch
+  let pendingMarkers = []; // Timed session markers waiting for the next epoch
+  let timedSessionTimer = null;
   let epochDeltas = [];
+  let epochEvents = []; // Typed records (undo/redo) pointing into epochDeltas
+  let epochCadence = new Array(CADENCE_BUCKETS_MS.length + 1).fill(0);
+  let lastKeystroke = null;
+  let historyOperation = null; // Set while Quill applies an undo or redo
+  let resumedFrom = null; // Set when a saved document is reopened
   let isVDFRunning = false;
   let epochStartTime = Date.now();
   let currentProgress = 0;
   let vdfWorker;
+  let vdfFailures = 0;
+  let vdfRetryTimer = null;
 
   // Initialize the globally declared quill instance
   quill = new Quill('#editor', { theme: 'snow', placeholder: 'Start writing...' });
@@ -939,11 +2646,11 @@ async function run() {
 
     doc.setFont('helvetica', 'bold');
     doc.setFontSize(12);
-    doc.text('✓ Document Verified', 15, stampY + 8);
+    doc.text(results.seeded ? 'DEMO ONLY: seeded document, simulated clock' : '✓ Document Verified', 15, stampY + 8);
 
     doc.setFont('courier', 'normal');
     doc.setFontSize(9);
-    doc.text(`Verification Date: ${new Date().toLocaleString()}`, 15, stampY + 16);
+    doc.text(`Verification Date: ${formatTime(new Date())}`, 15, stampY + 16);
     doc.text(`Epochs Verified:   ${results.verifiedEpochs}/${results.totalEpochs}`, 15, stampY + 21);
     doc.text(`Author Signature:  ${results.signatureValid ? 'VALID' : 'INVALID'}`, 15, stampY + 26);
     doc.text(`Final Hash:        ${documentData.metadata.documentHash.substring(0, 48)}...`, 15, stampY + 31);
@@ -967,6 +2674,34 @@ async function run() {
     }
 
     doc.save(`${documentData.title.replace(/\s/g, '_')}_verified.pdf`);
+    emitDocumentEvent('exported', { kind: 'pdf' });
+  }
+
+  // --- Attestation Package ---
+  // Everything a third party needs to check the document on their own, in one file.
+  async function exportAttestationPackage(results, fullDocumentData) {
+    const documentData = scrubForExport(fullDocumentData);
+    // The package must still verify on the receiving end
+    if (await computeDocumentHash(documentData) !== documentData.metadata.documentHash) {
+      showToast('The scrubbed document no longer matches its signed hash; nothing was exported.', 'error');
+      return;
+    }
+    const pkg = {
+      format: ATTESTATION_PACKAGE_FORMAT,
+      version: 1,
+      createdAt: new Date().toISOString(),
+      title: documentData.title,
+      text: quill.getText(),
+      document: documentData,
+      verification: results,
+      ...(results.seeded ? { demoOnly: true } : {}),
+      authorship: calculateEnhancedHumanAuthorshipScore(documentData.proofChain),
+      publicKey: documentData.metadata.publicKey,
+      instructions: ATTESTATION_INSTRUCTIONS
+    };
+    downloadFile(`${safeFilename(documentData.title)}_attestation.json`, JSON.stringify(pkg, null, 2));
+    showToast('Attestation package exported', 'success');
+    emitDocumentEvent('exported', { kind: 'attestation-package' });
   }
 
   // --- MOVED FUNCTION ---
@@ -980,39 +2715,105 @@ async function run() {
     const signatureColor = results.signatureValid ? 'var(--primary-color)' : '#ff4444';
     const signatureText = results.signatureValid ? 'VALID' : 'INVALID / MISSING';
 
-    if (results.valid && results.signatureValid) {
+    // Only a check of the whole chain earns the success banner and the exports
+    const partial = results.range ? `Partially verified (epochs ${results.range.from}-${results.range.to})`
+      : results.truncated ? 'Partially verified (stopped early)'
+      : results.coverage?.skippedProofs > 0 ? `Sampled, not verified (${results.coverage.skippedProofs} VDF proofs not checked)` : null;
+    const verified = results.valid && results.signatureValid;
+
+    if (verified && partial) {
+      resultsEl.innerHTML = `<div style="color: #ffaa00; margin-bottom: 16px; font-size: 18px;">${partial}</div>`;
+    } else if (verified && results.seeded) {
+      resultsEl.innerHTML = `<div style="color: #ffaa00; margin-bottom: 16px; font-size: 18px;">Seeded demo document: valid, but not evidence of when it was written</div>`;
+    } else if (verified) {
       resultsEl.innerHTML = `<div style="color: var(--primary-color); margin-bottom: 16px; font-size: 18px;">✓ Document verified successfully</div>`;
     } else {
       resultsEl.innerHTML = `<div style="color: #ff4444; margin-bottom: 16px; font-size: 18px;">✗ Verification failed</div>
-                             <div style="color: #ff4444; margin-bottom: 16px;">${results.errors.join('<br>')}</div>`;
+                             <div style="color: #ff4444; margin-bottom: 16px;">${results.errors.map(escapeHtml).join('<br>')}</div>`;
+    }
+
+    if (results.warnings.length > 0) {
+      resultsEl.innerHTML += `<div style="color: #ffaa00; margin-bottom: 16px;">${results.warnings.map(escapeHtml).join('<br>')}</div>`;
     }
 
     resultsEl.innerHTML += `
         <div style="margin-bottom: 8px;">Verified Epochs: ${results.verifiedEpochs}/${results.totalEpochs}</div>
+        <div style="margin-bottom: 8px;">Mode: ${results.strict ? 'Strict' : 'Standard'}${results.cached ? ' (cached result)' : ''}</div>
+        ${results.range ? `<div style="margin-bottom: 8px;">Range: epochs ${results.range.from}-${results.range.to} of ${documentData.proofChain.length - 1}</div>` : ''}
+        ${results.coverage ? `<div style="margin-bottom: 8px;">Coverage: ${results.coverage.checkedProofs} of ${results.coverage.checkedProofs + results.coverage.skippedProofs} VDF proofs checked within the ${results.coverage.budgetSeconds}s budget</div>` : ''}
+        ${results.truncated ? `<div style="margin-bottom: 8px; color: #ffaa00;">Stopped early: only epochs up to the first findings were checked</div>` : ''}
+        <div style="margin-bottom: 8px;">Writing Sessions: ${results.sessions}</div>
+        <div style="margin-bottom: 8px;">Attested Undos: ${results.retractions}</div>
+        ${(results.timedSessions || []).map(t => `<div style="margin-bottom: 8px;">${t.exam ? 'Exam' : 'Timed'} Session: epochs ${t.start}-${t.end ?? 'open'}, ${escapeHtml(t.minutes)} min declared, ${(t.writingSeconds / 60).toFixed(1)} min attested</div>`).join('')}
+        ${results.clockDrift?.epochs > 0 ? `<div style="margin-bottom: 8px;">Clock Drift: ${results.clockDrift.vdfSeconds.toFixed(0)}s of VDF work took ${results.clockDrift.clockSeconds.toFixed(0)}s by the system clock over ${results.clockDrift.epochs} epochs${results.clockDrift.runs.length > 0 ? ` (${results.clockDrift.runs.length} sustained divergences)` : ''}</div>` : ''}
+        ${(results.custody || []).length > 0 ? `<div style="margin-bottom: 8px;">Custody Transfers: ${results.custody.map(t => `${formatTime(t.at, 'date')}${t.accepted ? '' : ' (not accepted)'}`).join(' → ')}</div>` : ''}
+        <div style="margin-bottom: 8px;">Produced By: ${escapeHtml(describeProvenance(documentData.provenance))}</div>
         <div style="margin-bottom: 8px;">Author Signature: <span style="color:${signatureColor};">${signatureText}</span></div>
-        <div style="margin-bottom: 16px;">Final Hash: ${documentData.metadata.latestHash.substring(0, 16)}...</div>
+        <div style="margin-bottom: 16px;">Final Hash: ${escapeHtml(documentData.metadata.latestHash.substring(0, 16))}...</div>
         ${authorshipHTML}`; // Display the analysis
 
-    if (results.valid && results.signatureValid) {
+    if (verified && !partial) {
       document.getElementById('export-pdf-btn').style.display = 'block';
       document.getElementById('export-pdf-btn').onclick = () => exportToVerifiedPDF(results, documentData);
+      document.getElementById('export-package-btn').style.display = 'block';
+      document.getElementById('export-package-btn').onclick = () => exportAttestationPackage(results, documentData);
     } else {
       document.getElementById('export-pdf-btn').style.display = 'none';
+      document.getElementById('export-package-btn').style.display = 'none';
     }
   }
 
+  // The last verification shown in the modal, so it can be re-run when options change
+  let lastVerification = null;
+
+  async function runVerification(documentData, verify) {
+    lastVerification = { documentData, verify };
+    showVerificationModal();
+    updateVerificationProgress(0, 'Starting verification...');
+    const startedAt = performance.now();
+    const verification = await verify(updateVerificationProgress, {
+      strict: settings.strictVerification,
+      stopOnError: settings.stopOnFirstError,
+      maxFindings: settings.maxFindings,
+      range: parseEpochRange(document.getElementById('verify-range').value),
+      timeBudget: Number(document.getElementById('verify-budget').value) || 0,
+      onDetail: appendVerificationDetail
+    });
+    if (!verification.cached) recordMetric('verificationSeconds', (performance.now() - startedAt) / 1000);
+    showVerificationResults(verification, documentData);
+    emitDocumentEvent('verified', { results: verification });
+    renderProofChain(proofChain, 'verified', verification.errors);
+  }
+
   function startNextEpoch() {
     if (!proofChain || proofChain.length === 0) return;
     isVDFRunning = true;
     epochStartTime = Date.now();
     currentProgress = 0;
     const previousEpoch = proofChain[proofChain.length - 1];
-    vdfWorker.postMessage({ command: 'start', input: previousEpoch.hash, iterations: calibratedIterations });
+    runningIterations = calibratedIterations;
+    vdfWorker.postMessage({ command: 'start', backend: vdfProvenance.vdfBackend, input: previousEpoch.hash, iterations: runningIterations, logLevel: settings.logLevel });
+  }
+
+  // Unsaved (isDirty) and unattested (epochDeltas) are tracked separately: a saved
+  // document can still hold edits that no epoch has bound yet.
+  function updatePendingStatus() {
+    emitDocumentEvent('pending-changed', { count: epochDeltas.length });
   }
 
   function restartVdfProcess() {
     if (vdfWorker) vdfWorker.terminate();
+    clearTimeout(vdfRetryTimer);
+    vdfFailures = 0;
+    anchorRequested = false;
+    pendingMarkers = [];
+    clearTimeout(timedSessionTimer);
+    timedSessionTimer = null;
     epochDeltas = [];
+    epochEvents = [];
+    epochCadence.fill(0);
+    lastKeystroke = null;
+    updatePendingStatus();
     vdfWorker = new Worker(new URL('./vdf-worker.js', import.meta.url));
     vdfWorker.onmessage = async (event) => {
       const { status, proof, progress, error } = event.data;
@@ -1023,19 +2824,62 @@ async function run() {
       if (status === 'error') {
         console.error('VDF Worker Error:', error);
         isVDFRunning = false;
+        // Never fall back to an unproven epoch: keep the edits pending and retry
+        vdfFailures++;
+        if (vdfFailures > VDF_MAX_RETRIES) {
+          document.getElementById('timer-countdown').textContent = 'VDF failed';
+          showToast('VDF computation keeps failing. New edits are not being attested; save your work and reload.', 'error');
+          emitDocumentEvent('vdf-failed', { error });
+          return;
+        }
+        const delay = 1000 * 2 ** (vdfFailures - 1);
+        showToast(`VDF computation failed, retrying in ${delay / 1000}s...`, 'warning');
+        vdfRetryTimer = setTimeout(startNextEpoch, delay);
         return;
       }
       if (status === 'complete') {
-        const epochDuration = (Date.now() - epochStartTime) / 1000;
-        if (epochDeltas.length > 0) {
+        vdfFailures = 0;
+        const epochDuration = simulatedTime === null ? (Date.now() - epochStartTime) / 1000 : settings.epochSeconds;
+        // An anchor epoch binds a loaded document to this session even without edits
+        const anchoring = (anchorRequested && resumedFrom) || pendingMarkers.length > 0;
+        if ((epochDeltas.length > 0 && (settings.commitPolicy === 'auto' || commitRequested)) || anchoring) {
+          commitRequested = false;
+          anchorRequested = false;
+          if (simulatedTime !== null) simulatedTime += settings.epochSeconds * 1000;
           const prev = proofChain[proofChain.length - 1];
-          const epoch = new EpochProof(proofChain.length, prev.hash, epochDeltas, proof, calibratedIterations, epochDuration);
+          const epoch = new EpochProof(proofChain.length, prev.hash, epochDeltas, proof, runningIterations, epochDuration);
+          if (calibration) epoch.declaredRate = Math.round(calibration.iterationsPerSecond);
+          if (seed !== null) epoch.seeded = true;
+          const events = [...epochEvents, ...pendingMarkers];
+          if (events.length > 0) epoch.events = events;
+          pendingMarkers = [];
+          if (settings.typingCadence && epochCadence.some(count => count > 0)) {
+            epoch.cadence = { bucketsMs: CADENCE_BUCKETS_MS, counts: [...epochCadence] };
+            epoch.cadenceHash = await sha256(canonicalJson(epoch.cadence));
+          }
+          if (prev.iterations !== undefined && prev.iterations !== runningIterations) {
+            // Record why the difficulty moved, e.g. a document resumed on another machine
+            epoch.difficultyChange = {
+              previousIterations: prev.iterations,
+              newIterations: runningIterations,
+              reason: difficultyReason,
+              targetSeconds: settings.epochSeconds,
+              ...(calibration || {})
+            };
+          }
+          if (resumedFrom) {
+            epoch.resumedFrom = resumedFrom;
+            resumedFrom = null;
+          }
           await epoch.computeHash();
+          if (settings.dualHash) epoch.hashSha3 = epochHashSha3(epoch, prev);
           proofChain.push(epoch);
-          document.getElementById('epoch-number').textContent = epoch.epochNumber;
-          renderProofChain(proofChain);
           epochDeltas = [];
+          epochEvents = [];
+          epochCadence.fill(0);
           appState.isDirty = true;
+          emitDocumentEvent('epoch-created', { epoch });
+          updatePendingStatus();
         }
         startNextEpoch();
       }
@@ -1044,6 +2888,13 @@ async function run() {
   }
 
   function loadDocumentState(docData, key) {
+    const backend = docData.provenance?.vdfBackend || RSA_BACKEND;
+    if (!VDF_BACKENDS[backend]) {
+      showToast(`"${docData.title}" uses the unsupported VDF backend "${backend}".`, 'error');
+      return false;
+    }
+    if (!confirmModulus(docData)) return false;
+    if (backend !== vdfProvenance.vdfBackend) useBackend(backend);
     quill.setContents(docData.content.delta);
     proofChain = docData.proofChain;
     document.getElementById('document-title').value = docData.title || 'Untitled Document';
@@ -1052,13 +2903,69 @@ async function run() {
 
     // **FIX**: Store the original document's metadata
     appState.loadedMetadata = docData.metadata;
+    appState.loadedProvenance = docData.provenance || null;
+    appState.custody = docData.custody || [];
+    appState.bookmarks = docData.bookmarks || [];
+    acceptCustody(docData);
 
     const lastEpoch = docData.proofChain[docData.proofChain.length - 1];
-    document.getElementById('epoch-number').textContent = lastEpoch.epochNumber;
-    document.getElementById('last-saved').textContent = `Saved ${new Date(docData.timestamp).toLocaleTimeString()}`;
-    renderProofChain(proofChain);
+    resumedFrom = { epochNumber: lastEpoch.epochNumber, hash: lastEpoch.hash, timestamp: lastEpoch.timestamp };
+    emitDocumentEvent('loaded', { docData });
     updateWordCount(quill);
     restartVdfProcess();
+    return true;
+  }
+
+  // Countersign a transfer addressed to the loaded keys. The received version is
+  // stored as its sender signed it, since the transfer only certifies that version.
+  async function acceptCustody(docData) {
+    const last = appState.custody[appState.custody.length - 1];
+    if (!userKeys || !last || last.countersignature) return;
+    if (!sameKey(last.to, await cryptoHelpers.exportKey(userKeys.signingKeys.publicKey))) return;
+    appState.custody = [...appState.custody.slice(0, -1), await countersignTransfer(last, userKeys.signingKeys)];
+    appState.currentDocumentKey = appState.currentDocumentKey || LS_PREFIX + Date.now();
+    await saveToLocal(appState.currentDocumentKey, { ...docData, custody: appState.custody }, userKeys.encryptionKey);
+    showToast('Custody transfer accepted and saved with your countersignature.', 'success');
+  }
+
+  function startTimedSession(minutes = Number(prompt('Session length in minutes:', '60')), exam = false) {
+    if (timedSessionTimer) { showToast('A timed session is already running.', 'error'); return; }
+    if (!(minutes > 0)) return;
+    pendingMarkers.push({ type: 'session-start', minutes, ...(exam ? { exam: true } : {}) });
+    timedSessionTimer = setTimeout(endTimedSession, minutes * 60 * 1000);
+    showToast(`Timed session of ${minutes} minutes starts with the next epoch.`, 'info');
+  }
+
+  function endTimedSession() {
+    if (!timedSessionTimer) return;
+    clearTimeout(timedSessionTimer);
+    timedSessionTimer = null;
+    pendingMarkers.push({ type: 'session-end' });
+    showToast('Timed session ends with the next epoch.', 'info');
+  }
+
+  async function transferCustody() {
+    if (!userKeys || !appState.currentDocumentKey || appState.isDirty) {
+      showToast('Sign and save the document before transferring it.', 'error');
+      return;
+    }
+    const input = prompt("Paste the recipient's public key:");
+    if (!input) return;
+    let recipientKey;
+    try {
+      recipientKey = JSON.parse(input);
+      await cryptoHelpers.importKey(recipientKey, 'ECDSA', ['verify']);
+    } catch (e) {
+      showToast('That is not a valid public key.', 'error');
+      return;
+    }
+    const docData = await loadFromLocal(appState.currentDocumentKey, userKeys.encryptionKey);
+    if (!docData) return;
+    docData.custody = [...(docData.custody || []), await createTransferRecord(docData, recipientKey, userKeys.signingKeys)];
+    await saveToLocal(appState.currentDocumentKey, docData, userKeys.encryptionKey);
+    appState.custody = docData.custody;
+    downloadFile(`${safeFilename(docData.title)}.json`, JSON.stringify(scrubForExport(docData), null, 2));
+    showToast('Transfer recorded. Send the downloaded file to the recipient.', 'success');
   }
 
   function createNewDocument() {
@@ -1069,24 +2976,143 @@ async function run() {
 
     // **FIX**: Clear the metadata for a new document
     appState.loadedMetadata = null;
+    appState.loadedProvenance = null;
+    appState.custody = [];
+    appState.bookmarks = [];
+    resumedFrom = null;
 
     document.getElementById('document-title').value = "Untitled Document";
-    document.getElementById('last-saved').textContent = 'Not saved';
-    proofChain = [{ epochNumber: 0, hash: "0000000000000000000000000000000000000000000000000000000000000000", timestamp: new Date().toISOString() }];
-    renderProofChain(proofChain);
+    proofChain = [{ epochNumber: 0, hash: "0000000000000000000000000000000000000000000000000000000000000000", timestamp: now().toISOString() }];
+    if (VDF_BACKENDS[settings.vdfBackend] && settings.vdfBackend !== vdfProvenance.vdfBackend) useBackend(settings.vdfBackend);
+    emitDocumentEvent('created');
     restartVdfProcess();
     showToast("New document created.", "info");
   }
 
+  // --- Document Event Subscribers ---
+  const showEpochNumber = (number) => { document.getElementById('epoch-number').textContent = number; };
+  const showLastSaved = (text) => { document.getElementById('last-saved').textContent = text; };
+  onDocumentEvent('epoch-created', ({ epoch }) => {
+    recordMetric('epochSeconds', epoch.epochDuration);
+    recordMetric('iterationsPerSecond', epoch.iterations / epoch.epochDuration);
+    showEpochNumber(epoch.epochNumber);
+    renderProofChain(proofChain);
+  });
+  onDocumentEvent('pending-changed', ({ count }) => {
+    const el = document.getElementById('pending-changes');
+    el.textContent = count > 0 ? `${count} unattested edits` : 'All edits attested';
+    el.classList.toggle('pending', count > 0);
+  });
+  onDocumentEvent('saved', ({ docData }) => {
+    appendRootFeed(appState.currentDocumentKey, docData, userKeys.signingKeys);
+    recordMetric('saveBytes', JSON.stringify(docData).length);
+    showLastSaved(`Saved ${formatTime(new Date(), 'time')}`);
+  });
+  onDocumentEvent('loaded', ({ docData }) => {
+    showEpochNumber(docData.proofChain[docData.proofChain.length - 1].epochNumber);
+    showLastSaved(`Saved ${formatTime(docData.timestamp, 'time')}`);
+    renderProofChain(proofChain);
+  });
+  // Desktop notifications only fire while the tab is in the background
+  const notify = (title, body) => {
+    if (settings.desktopNotifications && document.hidden && 'Notification' in window && Notification.permission === 'granted') {
+      new Notification(title, { body });
+    }
+  };
+  onDocumentEvent('verified', ({ results }) => notify(
+    results.valid && results.signatureValid ? 'Verification passed' : 'Verification failed',
+    `${results.verifiedEpochs}/${results.totalEpochs} epochs verified, ${results.errors.length} errors`
+  ));
+  onDocumentEvent('save-failed', () => notify('Save failed', 'BitQuill could not save the document. Storage may be full.'));
+  onDocumentEvent('vdf-failed', () => notify('Attestation stopped', 'VDF computation keeps failing; new edits are not being attested.'));
+  onDocumentEvent('created', () => {
+    showEpochNumber(0);
+    showLastSaved('Not saved');
+    renderProofChain(proofChain);
+  });
+
   // --- UI Event Handlers ---
+  quill.root.addEventListener('keydown', (e) => {
+    if (!settings.typingCadence || e.repeat) return;
+    const now = performance.now();
+    if (lastKeystroke !== null) epochCadence[cadenceBucket(now - lastKeystroke)]++;
+    lastKeystroke = now;
+  });
+  // Quill's history bindings run on the same keydown, so flag it before they do
+  quill.root.addEventListener('keydown', (e) => {
+    if (!(e.ctrlKey || e.metaKey) || !['z', 'y'].includes(e.key.toLowerCase())) return;
+    historyOperation = e.key.toLowerCase() === 'y' || e.shiftKey ? 'redo' : 'undo';
+    setTimeout(() => { historyOperation = null; }, 0);
+  }, true);
+
   quill.on('text-change', (delta, oldDelta, source) => {
+    appState.bookmarks.forEach(bookmark => { bookmark.index = delta.transformPosition(bookmark.index); });
     if (source === 'user') {
       epochDeltas.push(delta);
+      if (historyOperation) epochEvents.push({ type: historyOperation, delta: epochDeltas.length - 1 });
       appState.isDirty = true;
+      updatePendingStatus();
+      if (settings.commitPolicy === 'paragraph') requestParagraphCommit();
     }
     updateWordCount(quill);
   });
 
+  // Paragraph policy: a paragraph counts as finished when the cursor leaves it
+  // after editing, or when it ends a sentence and typing pauses.
+  const SENTENCE_IDLE_MS = 2000;
+  function requestParagraphCommit() {
+    const selection = quill.getSelection();
+    if (!selection) return;
+    const line = quill.getLine(selection.index)[0];
+    if (editedLine && line !== editedLine) commitRequested = true; // Enter moved on to a new paragraph
+    editedLine = line;
+    clearTimeout(sentenceIdleTimer);
+    const before = quill.getText(Math.max(0, selection.index - 1), 1);
+    if (/[.!?]/.test(before)) sentenceIdleTimer = setTimeout(() => { commitRequested = true; }, SENTENCE_IDLE_MS);
+  }
+  quill.on('selection-change', (range) => {
+    if (settings.commitPolicy !== 'paragraph' || !range || !editedLine) return;
+    if (quill.getLine(range.index)[0] !== editedLine) {
+      commitRequested = true;
+      editedLine = null;
+    }
+  });
+
+  quill.root.addEventListener('copy', (e) => {
+    const selection = quill.getSelection();
+    if (!settings.attestedCopy || !selection || selection.length === 0) return;
+    if (epochDeltas.length > 0) {
+      showToast('Copied without attestation: the document has unattested edits.', 'info');
+      return;
+    }
+    const title = document.getElementById('document-title').value;
+    const head = signedHeadEntry(appState.currentDocumentKey, proofChain[proofChain.length - 1].hash);
+    const quote = buildQuote(proofChain, quill.getText(), selection.index, selection.length, title,
+      appState.loadedProvenance || vdfProvenance, head, appState.loadedMetadata?.publicKey);
+    if (quote) e.clipboardData.setData(QUOTE_MIME, JSON.stringify(quote));
+  });
+  quill.root.addEventListener('paste', async (e) => {
+    const payload = e.clipboardData.getData(QUOTE_MIME);
+    if (!payload) return;
+    try {
+      const quote = JSON.parse(payload);
+      const { valid, errors, epochs, signed } = await verifyQuote(quote);
+      const head = signed ? 'head signed by its author, ' : 'head not signed, ';
+      if (valid) showToast(`Quote from "${quote.title}" verified (${head}typed in epochs ${epochs.join(', ')}).`, 'success');
+      else showToast(`Quote from "${quote.title}" failed verification: ${errors[0]}`, 'error');
+    } catch (error) {
+      showToast(`Could not verify pasted quote: ${error.message}`, 'error');
+    }
+  });
+
+  window.addEventListener('beforeunload', (e) => {
+    // Unattested edits are lost on quit even if the file itself was saved
+    if (appState.isDirty || epochDeltas.length > 0) {
+      e.preventDefault();
+      e.returnValue = '';
+    }
+  });
+
   document.getElementById('generate-keys-btn').onclick = generateAndStoreKeys;
   document.getElementById('new-doc-btn').onclick = createNewDocument;
   document.getElementById('save-btn').onclick = signAndSaveDocument;
@@ -1113,7 +3139,9 @@ async function run() {
         ...currentDocData.metadata,
         signature: appState.loadedMetadata.signature,
         publicKey: appState.loadedMetadata.publicKey,
-        documentHash: appState.loadedMetadata.documentHash
+        documentHash: appState.loadedMetadata.documentHash,
+        documentHashSha3: appState.loadedMetadata.documentHashSha3,
+        signatureSha3: appState.loadedMetadata.signatureSha3
       }
     };
 
@@ -1123,20 +3151,20 @@ async function run() {
     }
 
     // Proceed with verification using the original signature data.
-    showVerificationModal();
-    updateVerificationProgress(0, 'Starting verification...');
-    const verification = await verifyDocument(docDataToVerify, updateVerificationProgress);
-    showVerificationResults(verification, docDataToVerify);
-    renderProofChain(proofChain, 'verified', verification.errors);
+    await runVerification(docDataToVerify, (progress, options) => verifyDocument(docDataToVerify, progress, options));
   };
 
   const fileBrowserModal = document.getElementById('file-browser-modal');
   const modalBackdrop = document.getElementById('modal-backdrop');
 
-  const showFileBrowser = () => {
+  let browsingVersions = false;
+  const showFileBrowser = async () => {
     const listEl = document.getElementById('file-browser-list');
     listEl.innerHTML = '';
-    const docs = listLocalDocs();
+    browsingVersions = false;
+    document.getElementById('versions-btn').textContent = 'Versions';
+    document.getElementById('store-select').value = localStorage.getItem(LS_STORE) || 'local';
+    const docs = await listLocalDocs();
     if (docs.length === 0) {
       listEl.innerHTML = '<p style="color: var(--text-secondary);">No documents saved in this browser.</p>';
     } else {
@@ -1144,13 +3172,15 @@ async function run() {
         const item = document.createElement('div');
         item.className = 'file-browser-item';
         item.innerHTML = `
-          <div class="file-item-info" data-key="${doc.key}">
-            <div class="file-browser-title">${doc.title}</div>
-            <div class="file-browser-timestamp">Saved: ${new Date(doc.timestamp).toLocaleString()}</div>
+          <div class="file-item-info" data-key="${escapeHtml(doc.key)}">
+            <div class="file-browser-title">${escapeHtml(doc.title)}</div>
+            <div class="file-browser-timestamp">Saved: ${formatTime(doc.timestamp)}</div>
           </div>
           <div class="file-browser-actions">
-            <button class="btn-icon export-btn" title="Export to File" data-key="${doc.key}"><svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><path d="M21 15v4a2 2 0 0 1-2 2H5a2 2 0 0 1-2-2v-4"></path><polyline points="7 10 12 15 17 10"></polyline><line x1="12" y1="15" x2="12" y2="3"></line></svg></button>
-            <button class="btn-icon delete-btn" title="Delete" data-key="${doc.key}"><svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><polyline points="3 6 5 6 21 6"></polyline><path d="M19 6v14a2 2 0 0 1-2 2H7a2 2 0 0 1-2-2V6m3 0V4a2 2 0 0 1 2-2h4a2 2 0 0 1 2 2v2"></path></svg></button>
+            <button class="btn-icon export-btn" title="Export to File" data-key="${escapeHtml(doc.key)}"><svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><path d="M21 15v4a2 2 0 0 1-2 2H5a2 2 0 0 1-2-2v-4"></path><polyline points="7 10 12 15 17 10"></polyline><line x1="12" y1="15" x2="12" y2="3"></line></svg></button>
+            <button class="btn-icon export-jsonl-btn" title="Export Epochs as JSON Lines" data-key="${escapeHtml(doc.key)}"><svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><line x1="8" y1="6" x2="21" y2="6"></line><line x1="8" y1="12" x2="21" y2="12"></line><line x1="8" y1="18" x2="21" y2="18"></line><line x1="3" y1="6" x2="3.01" y2="6"></line><line x1="3" y1="12" x2="3.01" y2="12"></line><line x1="3" y1="18" x2="3.01" y2="18"></line></svg></button>
+            <button class="btn-icon export-csv-btn" title="Export Epochs as CSV" data-key="${escapeHtml(doc.key)}"><svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><rect x="3" y="3" width="18" height="18" rx="2"></rect><line x1="3" y1="9" x2="21" y2="9"></line><line x1="3" y1="15" x2="21" y2="15"></line><line x1="9" y1="3" x2="9" y2="21"></line></svg></button>
+            <button class="btn-icon delete-btn" title="Delete" data-key="${escapeHtml(doc.key)}"><svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><polyline points="3 6 5 6 21 6"></polyline><path d="M19 6v14a2 2 0 0 1-2 2H7a2 2 0 0 1-2-2V6m3 0V4a2 2 0 0 1 2-2h4a2 2 0 0 1 2 2v2"></path></svg></button>
           </div>`;
         listEl.appendChild(item);
       });
@@ -1158,13 +3188,59 @@ async function run() {
     modalBackdrop.classList.add('show');
     fileBrowserModal.classList.add('show');
   };
+  const showVersions = async () => {
+    const listEl = document.getElementById('file-browser-list');
+    const versions = await listVersions();
+    listEl.innerHTML = versions.length === 0
+      ? '<p style="color: var(--text-secondary);">No earlier versions in this store.</p>'
+      : '';
+    versions.forEach(version => {
+      const item = document.createElement('div');
+      item.className = 'file-browser-item';
+      item.innerHTML = `
+        <div class="file-item-info">
+          <div class="file-browser-title">${escapeHtml(version.title)}</div>
+          <div class="file-browser-timestamp">Saved: ${formatTime(version.timestamp)} · Head ${escapeHtml(version.latestHash.substring(0, 16))}...</div>
+        </div>
+        <div class="file-browser-actions">
+          <button class="btn btn-secondary restore-btn" title="Restore this version" data-key="${escapeHtml(version.key)}">Restore</button>
+          <button class="btn-icon purge-btn" title="Delete permanently" data-key="${escapeHtml(version.key)}"><svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><polyline points="3 6 5 6 21 6"></polyline><path d="M19 6v14a2 2 0 0 1-2 2H7a2 2 0 0 1-2-2V6m3 0V4a2 2 0 0 1 2-2h4a2 2 0 0 1 2 2v2"></path></svg></button>
+        </div>`;
+      listEl.appendChild(item);
+    });
+  };
   const hideFileBrowser = () => {
     modalBackdrop.classList.remove('show');
     fileBrowserModal.classList.remove('show');
   };
 
   document.getElementById('browse-local-btn').onclick = showFileBrowser;
+  document.getElementById('store-select').onchange = async (e) => {
+    const from = activeStore();
+    const to = documentStores[e.target.value];
+    const count = (await from.list(LS_PREFIX)).length;
+    let moved = false;
+    if (count > 0 && confirm(`Move your ${count} saved documents and their versions from ${from.name} to ${to.name}?`)) {
+      try {
+        await migrateStore(from, to);
+        moved = true;
+        showToast(`Moved ${count} documents to ${to.name}.`, 'success');
+      } catch (error) {
+        console.error("Store migration failed:", error);
+        showToast(`Could not move documents: ${error.message}. They are still in ${from.name}.`, 'error');
+      }
+    }
+    localStorage.setItem(LS_STORE, e.target.value);
+    if (!moved) appState.currentDocumentKey = null; // Next save goes to the newly selected store
+    showFileBrowser();
+  };
   document.getElementById('file-browser-close').onclick = hideFileBrowser;
+  document.getElementById('versions-btn').onclick = async (e) => {
+    if (browsingVersions) return showFileBrowser();
+    browsingVersions = true;
+    e.target.textContent = 'Documents';
+    await showVersions();
+  };
 
   document.getElementById('file-browser-list').addEventListener('click', async (e) => {
     const target = e.target.closest('[data-key]');
@@ -1172,22 +3248,37 @@ async function run() {
     const key = target.getAttribute('data-key');
 
     if (e.target.closest('.delete-btn')) {
-      if (confirm(`Are you sure you want to delete this document? This cannot be undone.`)) {
-        deleteFromLocal(key);
+      if (confirm(`Delete this document? Its last saved version stays under Versions.`)) {
+        await deleteFromLocal(key);
         showToast("Document deleted", "info");
         showFileBrowser();
       }
+    } else if (e.target.closest('.restore-btn')) {
+      const documentKey = await restoreVersion(key);
+      showToast("Version restored. The replaced copy was kept under Versions.", "success");
+      if (documentKey === appState.currentDocumentKey) showToast("Reload the document to see the restored version.", "info");
+      showVersions();
+    } else if (e.target.closest('.purge-btn')) {
+      if (confirm(`Permanently delete this version? This cannot be undone.`)) {
+        await activeStore().remove(key);
+        showVersions();
+      }
+    } else if (e.target.closest('.export-jsonl-btn')) {
+      if (!userKeys) { showToast("Please load keys to export documents.", "error"); return; }
+      const docData = await loadFromLocal(key, userKeys.encryptionKey);
+      if (docData) {
+        const includeContent = confirm("Include the edits of each epoch? Cancel to export hashes only.");
+        downloadFile(`${safeFilename(docData.title)}_epochs.jsonl`, await epochsToJsonl(docData.proofChain, includeContent), 'application/x-ndjson');
+      }
+    } else if (e.target.closest('.export-csv-btn')) {
+      if (!userKeys) { showToast("Please load keys to export documents.", "error"); return; }
+      const docData = await loadFromLocal(key, userKeys.encryptionKey);
+      if (docData) downloadFile(`${safeFilename(docData.title)}_epochs.csv`, epochsToCsv(docData.proofChain), 'text/csv');
     } else if (e.target.closest('.export-btn')) {
       if (!userKeys) { showToast("Please load keys to export documents.", "error"); return; }
       const docData = await loadFromLocal(key, userKeys.encryptionKey);
       if (docData) {
-        const blob = new Blob([JSON.stringify(docData, null, 2)], { type: 'application/json' });
-        const url = URL.createObjectURL(blob);
-        const a = document.createElement('a');
-        a.href = url;
-        a.download = `${docData.title.replace(/[^a-z0-9]/gi, '_').toLowerCase()}.json`;
-        a.click();
-        URL.revokeObjectURL(url);
+        downloadFile(`${safeFilename(docData.title)}.json`, JSON.stringify(scrubForExport(docData), null, 2));
         showToast("Exporting document...", "info");
       }
     } else if (e.target.closest('.file-item-info')) {
@@ -1195,8 +3286,7 @@ async function run() {
       if (appState.isDirty && !confirm("You have unsaved changes that will be lost. Load document anyway?")) return;
 
       const docData = await loadFromLocal(key, userKeys.encryptionKey);
-      if (docData) { // loadFromLocal returns null on decryption failure
-        loadDocumentState(docData, key);
+      if (docData && loadDocumentState(docData, key)) { // loadFromLocal returns null on decryption failure
         hideFileBrowser();
         showToast(`Loaded "${docData.title}"`, "success");
       }
@@ -1204,13 +3294,53 @@ async function run() {
   });
 
   document.getElementById('file-input').onchange = async (e) => {
-    if (!e.target.files[0]) return;
+    const file = e.target.files[0];
+    if (!file) return;
+    // The whole history is parsed into memory, which can exhaust a tab on huge files
+    if (file.size > LARGE_IMPORT_BYTES && !confirm(
+      `"${file.name}" is ${(file.size / 1024 / 1024).toFixed(0)} MB. Importing loads its full history into memory ` +
+      `and may make the page unresponsive. Import anyway?`
+    )) {
+      e.target.value = '';
+      return;
+    }
     showToast('Importing and verifying...', 'info');
     try {
-      const fileContent = await e.target.files[0].text();
+      const fileContent = await file.text();
       const data = JSON.parse(fileContent);
+      if (data.format === HEAD_LOG_FORMAT) {
+        // Checked against the open document without replacing it
+        const { valid, errors } = await verifyHeadLog(data, proofChain);
+        if (valid) showToast(`Head log verified: ${data.entries.length} signed heads, all in this document's history.`, 'success');
+        else showToast(`Head log problems: ${errors.slice(0, 3).join(' ')}${errors.length > 3 ? ` (+${errors.length - 3} more)` : ''}`, 'error');
+        return;
+      }
       if (appState.isDirty && !confirm("You have unsaved changes that will be lost. Import anyway?")) return;
-      loadDocumentState(data, null); // Imported docs don't have a local key yet
+      if (data.format === DOCUMENT_DELTA_FORMAT) {
+        // Applied on top of the open document, which must be the backup it extends,
+        // and only once the merged document verifies: signed, with content that
+        // hashes to the signed hash and a signed head that is the merged head
+        const merged = applyDelta({ proofChain }, data);
+        const { valid, errors } = await verifyDocument(merged, null, { strict: settings.strictVerification });
+        if (!merged.metadata?.signature) errors.push('The delta is not signed.');
+        else errors.push(...await contentHashErrors(merged));
+        if (merged.metadata?.latestHash !== merged.proofChain[merged.proofChain.length - 1].hash) {
+          errors.push('The signed head is not the head of the merged chain.');
+        }
+        if (!valid || errors.length > 0) {
+          showToast(`The merged document does not verify: ${errors.slice(0, 3).join(' ')}${errors.length > 3 ? ` (+${errors.length - 3} more)` : ''}`, 'error');
+          return;
+        }
+        if (!loadDocumentState(merged, appState.currentDocumentKey)) return;
+        showToast(`Applied ${data.epochs.length} new epochs.`, 'success');
+        return;
+      }
+      if (data.format === ATTESTATION_PACKAGE_FORMAT) {
+        if (!loadDocumentState(data.document, null)) return;
+        await runVerification(data.document, (progress, options) => verifyAttestationPackage(data, progress, options));
+        return;
+      }
+      if (!loadDocumentState(data, null)) return; // Imported docs don't have a local key yet
       showToast('Document imported successfully', 'success');
     } catch (error) {
       showToast(`Error importing file: ${error.message}`, 'error');
@@ -1219,20 +3349,565 @@ async function run() {
     }
   };
 
+  const compareModal = document.getElementById('compare-modal');
+  const hideCompare = () => {
+    modalBackdrop.classList.remove('show');
+    compareModal.classList.remove('show');
+  };
+  document.getElementById('compare-btn').onclick = () => document.getElementById('compare-input').click();
+  document.getElementById('compare-input').onchange = async (e) => {
+    const files = [...e.target.files];
+    e.target.value = '';
+    if (files.length !== 2) { showToast('Select exactly two documents to compare.', 'error'); return; }
+    try {
+      const [a, b] = await Promise.all(files.map(async file => {
+        const data = JSON.parse(await file.text());
+        return data.format === ATTESTATION_PACKAGE_FORMAT ? data.document : data;
+      }));
+      const result = compareDocuments(a, b);
+      const describe = (epochs) => epochs.length === 0 ? 'none' : `${epochs.length} (epochs ${epochs[0].epochNumber}-${epochs[epochs.length - 1].epochNumber})`;
+      const yesNo = (value) => (value ? 'yes' : 'no');
+      document.getElementById('compare-details').textContent = [
+        `First:  ${files[0].name} ("${a.title}", ${a.proofChain.length - 1} epochs)`,
+        `Second: ${files[1].name} ("${b.title}", ${b.proofChain.length - 1} epochs)`,
+        '',
+        `History: ${result.relation}`,
+        `Shared up to: ${result.base ? `epoch ${result.base.epochNumber} (${result.base.hash.substring(0, 16)}...)` : 'nothing'}`,
+        `Only in first: ${describe(result.firstOnly)}`,
+        `Only in second: ${describe(result.secondOnly)}`,
+        `Same signed hash: ${yesNo(result.sameDocumentHash)}`,
+        `Same signer: ${yesNo(result.sameSigner)}`,
+        `Same title: ${yesNo(result.sameTitle)}`
+      ].join('\n');
+      hideFileBrowser();
+      modalBackdrop.classList.add('show');
+      compareModal.classList.add('show');
+    } catch (error) {
+      showToast(`Could not compare documents: ${error.message}`, 'error');
+    }
+  };
+  document.getElementById('compare-close').onclick = hideCompare;
+  const hideExplain = () => {
+    modalBackdrop.classList.remove('show');
+    document.getElementById('explain-modal').classList.remove('show');
+  };
+  document.getElementById('explain-close').onclick = hideExplain;
+
   document.getElementById('epoch-indicator').onclick = () => document.getElementById('proof-panel').classList.toggle('show');
   document.getElementById('proof-panel-close').onclick = () => document.getElementById('proof-panel').classList.remove('show');
-  modalBackdrop.onclick = () => { hideVerificationModal(); hideFileBrowser(); };
+  document.getElementById('proof-query').oninput = () => renderProofChain(proofChain);
+  document.getElementById('anchor-btn').onclick = () => {
+    if (!resumedFrom) {
+      showToast('This session is already linked to the document.', 'info');
+      return;
+    }
+    anchorRequested = true;
+    showToast('The next epoch will anchor the document to this session.', 'info');
+  };
+  document.getElementById('blame-btn').onclick = () => {
+    // Uncommitted edits are attributed to the epoch currently being computed
+    const pending = { epochNumber: proofChain.length, deltas: epochDeltas, timestamp: now().toISOString() };
+    renderBlame([...proofChain, pending], quill.getText());
+  };
+  document.getElementById('outline-btn').onclick = () => {
+    const pending = { epochNumber: proofChain.length, deltas: epochDeltas, timestamp: now().toISOString() };
+    const levels = quill.getLines().map(line => line.formats().header || 0);
+    const sections = outlineSections([...proofChain, pending], quill.getText(), levels);
+    const proofDisplay = document.getElementById('proof-display');
+    if (!sections || sections.length === 0) {
+      proofDisplay.innerHTML = `<p style="color: var(--text-secondary);">${sections ? 'No headings. Start a line with # or use a heading format.' : 'History does not replay to the current text; the outline is unavailable.'}</p>`;
+      return;
+    }
+    proofDisplay.innerHTML = '';
+    sections.forEach(section => {
+      const entry = document.createElement('button');
+      entry.className = 'outline-entry';
+      entry.style.paddingLeft = `${section.level * 12}px`;
+      const status = section.to === pending.epochNumber ? 'has unattested edits' : 'attested';
+      entry.innerHTML = `<div></div><div style="color: var(--text-secondary);">Epochs #${escapeHtml(section.from)}-${escapeHtml(section.to)}, ${status}</div>`;
+      entry.firstElementChild.textContent = section.title;
+      entry.onclick = () => quill.setSelection(section.index, 0);
+      proofDisplay.appendChild(entry);
+    });
+  };
+
+  const historySearchModal = document.getElementById('history-search-modal');
+  const showHistorySearch = () => {
+    modalBackdrop.classList.add('show');
+    historySearchModal.classList.add('show');
+    document.getElementById('history-search-input').focus();
+  };
+  const conflictModal = document.getElementById('conflict-modal');
+  let externalDoc = null;
+  const hideConflict = () => {
+    modalBackdrop.classList.remove('show');
+    conflictModal.classList.remove('show');
+    externalDoc = null;
+  };
+  documentChannel.onmessage = async ({ data }) => {
+    if (!userKeys || data.key !== appState.currentDocumentKey) return;
+    externalDoc = await loadFromLocal(data.key, userKeys.encryptionKey);
+    if (!externalDoc) return;
+    document.getElementById('conflict-details').textContent = '';
+    modalBackdrop.classList.add('show');
+    conflictModal.classList.add('show');
+  };
+  document.getElementById('conflict-reload').onclick = () => {
+    const docData = externalDoc;
+    hideConflict();
+    loadDocumentState(docData, appState.currentDocumentKey);
+  };
+  document.getElementById('conflict-keep').onclick = () => {
+    // The next save deliberately replaces the other tab's version
+    appState.isDirty = true;
+    hideConflict();
+  };
+  document.getElementById('conflict-compare').onclick = () => {
+    const { base, localOnly, externalOnly } = compareChains(proofChain, externalDoc.proofChain);
+    const describe = (epochs) => epochs.length === 0 ? 'none' :
+      `${epochs.length} (epochs ${epochs[0].epochNumber}-${epochs[epochs.length - 1].epochNumber}, head ${epochs[epochs.length - 1].hash.substring(0, 16)}...)`;
+    document.getElementById('conflict-details').textContent =
+      `Shared base: ${base ? `epoch ${base.epochNumber} (${base.hash.substring(0, 16)}...)` : 'none'}\n` +
+      `Only in this tab: ${describe(localOnly)}\n` +
+      `Only in the saved copy: ${describe(externalOnly)}\n` +
+      `Saved copy timestamp: ${formatTime(externalDoc.timestamp)}`;
+  };
+
+  // --- Bookmarks ---
+  // Alt+B bookmarks the cursor position, Alt+J lists bookmarks to jump to.
+  const bookmarksModal = document.getElementById('bookmarks-modal');
+  const hideBookmarks = () => {
+    modalBackdrop.classList.remove('show');
+    bookmarksModal.classList.remove('show');
+  };
+  const addBookmark = () => {
+    if (auditing) return;
+    const index = (quill.getSelection() || { index: 0 }).index;
+    const name = prompt('Bookmark name:', `Bookmark ${appState.bookmarks.length + 1}`);
+    if (!name) return;
+    // Tie the bookmark to the epoch that wrote this spot, once it is attested
+    const owners = computeBlame(proofChain, quill.getText());
+    appState.bookmarks = [...appState.bookmarks, { name, index, epochNumber: owners ? owners[index] : null }];
+    appState.isDirty = true;
+    showToast(`Bookmarked "${name}"`, 'success');
+  };
+  const showBookmarks = () => {
+    const listEl = document.getElementById('bookmarks-list');
+    listEl.innerHTML = appState.bookmarks.length === 0
+      ? '<p style="color: var(--text-secondary);">No bookmarks. Press Alt+B in the editor to add one.</p>'
+      : '';
+    appState.bookmarks.forEach((bookmark, i) => {
+      const item = document.createElement('div');
+      item.className = 'file-browser-item';
+      item.dataset.bookmark = i;
+      item.innerHTML = `
+        <div class="file-item-info">
+          <div class="file-browser-title"></div>
+          <div class="file-browser-timestamp">Position ${escapeHtml(bookmark.index)}${bookmark.epochNumber !== null ? ` · Epoch #${escapeHtml(bookmark.epochNumber)}` : ''}</div>
+        </div>
+        <div class="file-browser-actions">
+          <button class="btn-icon delete-btn" title="Remove bookmark"><svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><line x1="18" y1="6" x2="6" y2="18"></line><line x1="6" y1="6" x2="18" y2="18"></line></svg></button>
+        </div>`;
+      item.querySelector('.file-browser-title').textContent = bookmark.name;
+      listEl.appendChild(item);
+    });
+    modalBackdrop.classList.add('show');
+    bookmarksModal.classList.add('show');
+  };
+  document.getElementById('bookmarks-list').addEventListener('click', (e) => {
+    const item = e.target.closest('[data-bookmark]');
+    if (!item) return;
+    const index = Number(item.dataset.bookmark);
+    if (e.target.closest('.delete-btn')) {
+      if (auditing) return;
+      appState.bookmarks = appState.bookmarks.filter((_, i) => i !== index);
+      appState.isDirty = true;
+      showBookmarks();
+      return;
+    }
+    hideBookmarks();
+    quill.setSelection(Math.min(appState.bookmarks[index].index, quill.getLength() - 1), 0);
+  });
+  document.getElementById('bookmarks-close').onclick = hideBookmarks;
+  document.addEventListener('keydown', (e) => {
+    if (!e.altKey || e.ctrlKey || e.metaKey) return;
+    if (e.code === 'KeyB') {
+      e.preventDefault();
+      addBookmark();
+    } else if (e.code === 'KeyJ') {
+      e.preventDefault();
+      showBookmarks();
+    }
+  });
+
+  const hideHistorySearch = () => {
+    modalBackdrop.classList.remove('show');
+    historySearchModal.classList.remove('show');
+  };
+  document.getElementById('history-search-input').oninput = (e) => {
+    const resultsEl = document.getElementById('history-search-results');
+    const matches = searchHistory(proofChain, e.target.value);
+    resultsEl.innerHTML = '';
+    matches.forEach(match => {
+      const item = document.createElement('div');
+      item.className = 'file-browser-item';
+      item.dataset.epoch = match.epochNumber;
+      item.innerHTML = `
+        <div class="file-item-info">
+          <div class="file-browser-title">Epoch #${escapeHtml(match.epochNumber)}</div>
+          <div class="file-browser-timestamp">${formatTime(match.timestamp)}</div>
+        </div>`;
+      const snippet = document.createElement('div');
+      snippet.className = 'history-search-snippet';
+      snippet.textContent = match.snippet;
+      item.firstElementChild.appendChild(snippet);
+      resultsEl.appendChild(item);
+    });
+    if (e.target.value && matches.length === 0) {
+      resultsEl.innerHTML = '<p style="color: var(--text-secondary);">No matches in the document history.</p>';
+    }
+  };
+  document.getElementById('history-search-results').addEventListener('click', (e) => {
+    const item = e.target.closest('[data-epoch]');
+    if (!item) return;
+    // Jump the proof panel to the epoch that contains the match
+    document.getElementById('proof-query').value = `${item.dataset.epoch}-${item.dataset.epoch}`;
+    renderProofChain(proofChain);
+    hideHistorySearch();
+    document.getElementById('proof-panel').classList.add('show');
+  });
+  document.getElementById('history-search-close').onclick = hideHistorySearch;
+  document.addEventListener('keydown', (e) => {
+    if (e.ctrlKey && e.shiftKey && e.key.toLowerCase() === 'f') {
+      e.preventDefault();
+      showHistorySearch();
+    }
+  });
+  modalBackdrop.onclick = () => { hideVerificationModal(); hideFileBrowser(); hideHistorySearch(); hideSettings(); hideConflict(); hidePalette(); hideCompare(); hideExplain(); hideBookmarks(); };
   document.getElementById('modal-close').onclick = hideVerificationModal;
 
+  const settingsModal = document.getElementById('settings-modal');
+  const hideSettings = () => {
+    modalBackdrop.classList.remove('show');
+    settingsModal.classList.remove('show');
+  };
+  const applySetting = (key) => {
+    if (key === 'logLevel') set_log_level(settings.logLevel);
+    if (key === 'desktopNotifications' && settings.desktopNotifications && 'Notification' in window) {
+      Notification.requestPermission();
+    }
+    if (key === 'epochSeconds' && calibration) {
+      // Takes effect from the next epoch, which records the change
+      calibratedIterations = iterationsFor(settings.epochSeconds);
+      difficultyReason = 'epoch-length';
+    }
+  };
+  document.getElementById('settings-btn').onclick = () => {
+    bindSettingsForm(settingsModal, applySetting);
+    modalBackdrop.classList.add('show');
+    settingsModal.classList.add('show');
+  };
+  document.getElementById('settings-close').onclick = hideSettings;
+
+  document.addEventListener('keydown', (e) => {
+    if ((e.ctrlKey || e.metaKey) && e.key === 'Enter' && settings.commitPolicy !== 'auto') {
+      e.preventDefault();
+      commitRequested = true;
+      showToast('Pending changes will be committed with the next epoch.', 'info');
+    }
+  });
+  const strictToggle = document.getElementById('strict-verification');
+  strictToggle.checked = settings.strictVerification;
+  strictToggle.onchange = () => {
+    updateSettings({ strictVerification: strictToggle.checked });
+    if (lastVerification) runVerification(lastVerification.documentData, lastVerification.verify);
+  };
+  ['verify-range', 'verify-budget'].forEach(id => {
+    document.getElementById(id).onchange = () => {
+      if (lastVerification) runVerification(lastVerification.documentData, lastVerification.verify);
+    };
+  });
+
+  // Every action reachable from the palette, by name. Commands may be async;
+  // macros wait for each one before running the next.
+  const click = (id) => () => document.getElementById(id).onclick();
+  const commands = {
+    'New Document': click('new-doc-btn'),
+    'Sign and Save': click('save-btn'),
+    'My Documents': click('browse-local-btn'),
+    'Import File': () => document.getElementById('file-input').click(),
+    'Verify Current Document': click('verify-live-btn'),
+    'Export Verified PDF': () => document.getElementById('export-pdf-btn').click(),
+    'Export Attestation Package': () => document.getElementById('export-package-btn').click(),
+    'Toggle Proof Chain': click('epoch-indicator'),
+    'Blame': click('blame-btn'),
+    'Outline': click('outline-btn'),
+    'Re-anchor': click('anchor-btn'),
+    'Search History': showHistorySearch,
+    'Bookmarks': showBookmarks,
+    'Add Bookmark': addBookmark,
+    'Compare Two Files': click('compare-btn'),
+    'Copy My Public Key': async () => {
+      if (!userKeys) { showToast('No signing keys loaded.', 'error'); return; }
+      await navigator.clipboard.writeText(JSON.stringify(await cryptoHelpers.exportKey(userKeys.signingKeys.publicKey)));
+      showToast('Public key copied.', 'success');
+    },
+    'Transfer Custody': transferCustody,
+    'Explain Verification': async () => {
+      const documentData = lastVerification?.documentData || await buildDocumentData();
+      document.getElementById('explain-title').textContent = 'What Verification Checks';
+      document.getElementById('explain-details').textContent = explainVerification(documentData, {
+        strict: settings.strictVerification,
+        stopOnError: settings.stopOnFirstError,
+        maxFindings: settings.maxFindings,
+        range: parseEpochRange(document.getElementById('verify-range').value),
+        timeBudget: Number(document.getElementById('verify-budget').value) || 0
+      }, calibration?.iterationsPerSecond);
+      modalBackdrop.classList.add('show');
+      document.getElementById('explain-modal').classList.add('show');
+    },
+    'Explain Proof': async () => {
+      const chain = proofChain;
+      if (chain.length < 2) { showToast('No VDF proofs yet: the genesis epoch has none.', 'info'); return; }
+      const input = prompt(`Epoch to explain (1-${chain.length - 1}):`, String(chain.length - 1));
+      const epoch = input && chain[Number(input)];
+      if (!epoch || !epoch.vdfProof) { if (input) showToast(`Epoch ${input} has no VDF proof.`, 'error'); return; }
+      const provenance = appState.loadedProvenance || vdfProvenance;
+      if (provenance?.vdfBackend === CLASS_GROUP_BACKEND) { showToast('Explain Proof only covers RSA documents.', 'info'); return; }
+      try {
+        document.getElementById('explain-details').textContent = await explainProofMath(epoch, provenance);
+      } catch (error) {
+        showToast(`Could not explain epoch ${input}: ${error.message ?? error}`, 'error');
+        return;
+      }
+      document.getElementById('explain-title').textContent = `The Math Behind Epoch ${epoch.epochNumber}`;
+      modalBackdrop.classList.add('show');
+      document.getElementById('explain-modal').classList.add('show');
+    },
+    'Export Root Feed': () => {
+      if (!appState.currentDocumentKey) { showToast('Save the document to start its feed.', 'info'); return; }
+      const title = document.getElementById('document-title').value;
+      downloadFile(`${safeFilename(title)}_feed.json`, JSON.stringify(rootFeed(appState.currentDocumentKey, title), null, 2), 'application/feed+json');
+    },
+    'Export Head Log': async () => {
+      if (!userKeys || !appState.currentDocumentKey) { showToast('Sign and save the document to start its log.', 'info'); return; }
+      const title = document.getElementById('document-title').value;
+      const publicKey = await cryptoHelpers.exportKey(userKeys.signingKeys.publicKey);
+      downloadFile(`${safeFilename(title)}_heads.json`, JSON.stringify(headLog(appState.currentDocumentKey, title, publicKey), null, 2));
+    },
+    'Compare Head Logs': async () => {
+      if (!userKeys || !appState.currentDocumentKey) { showToast('Sign and save the document to start its log.', 'info'); return; }
+      const source = prompt("URL of the other head log (leave empty to choose a file):");
+      if (source === null) return;
+      try {
+        const theirs = source ? await (await fetch(source)).json() : await pickJsonFile();
+        if (!theirs) return;
+        if (theirs.format !== HEAD_LOG_FORMAT) throw new Error('Not a BitQuill head log.');
+        const title = document.getElementById('document-title').value;
+        const publicKey = await cryptoHelpers.exportKey(userKeys.signingKeys.publicKey);
+        const { comparable, problems, equivocations, shared } = await compareHeadLogs(headLog(appState.currentDocumentKey, title, publicKey), theirs);
+        if (!comparable) showToast(`Logs can't be compared: ${problems.slice(0, 3).join(' ')}`, 'error');
+        else if (equivocations.length > 0) showToast(`Equivocation: ${equivocations.length} conflicting heads, first at epoch count ${equivocations[0].epochCount}.`, 'error');
+        else showToast(`Logs agree on ${shared} shared heads.`, 'success');
+      } catch (error) {
+        showToast(`Could not compare head logs: ${error.message}`, 'error');
+      }
+    },
+    'Export Incremental Backup': async () => {
+      if (!userKeys || !appState.currentDocumentKey) { showToast('Sign and save the document first.', 'info'); return; }
+      const docData = await loadFromLocal(appState.currentDocumentKey, userKeys.encryptionKey);
+      if (!docData) return;
+      const baseHash = prompt('Head hash of your last backup (shown in the proof panel):');
+      if (!baseHash) return;
+      try {
+        const delta = diffSince(scrubForExport(docData), baseHash.trim());
+        downloadFile(`${safeFilename(docData.title)}_since_${baseHash.trim().substring(0, 8)}.json`, JSON.stringify(delta, null, 2));
+      } catch (error) {
+        showToast(error.message, 'error');
+      }
+    },
+    'Export Metrics': () => {
+      if (!settings.collectMetrics) { showToast('Turn on metrics in Settings first.', 'info'); return; }
+      downloadFile('bitquill_metrics.json', JSON.stringify(metricsReport(), null, 2));
+    },
+    'Start Timed Session': () => startTimedSession(),
+    'End Timed Session': endTimedSession,
+    'Settings': click('settings-btn'),
+    'Generate Signing Keys': click('generate-keys-btn'),
+    'Start Recording Macro': () => {
+      recordingMacro = [];
+      showToast('Recording macro. Commands run from the palette are recorded.', 'info');
+    },
+    'Stop Recording Macro': () => {
+      if (!recordingMacro) return;
+      const steps = recordingMacro;
+      recordingMacro = null;
+      const name = steps.length > 0 && prompt(`Name this macro (${steps.join(' → ')}):`);
+      if (!name) return;
+      localStorage.setItem(LS_MACROS, JSON.stringify({ ...macros(), [name]: steps }));
+      showToast(`Macro "${name}" saved.`, 'success');
+    }
+  };
+  let recordingMacro = null;
+  const macros = () => JSON.parse(localStorage.getItem(LS_MACROS)) || {};
+  const runMacro = async (steps) => {
+    for (const step of steps) {
+      if (!commands[step]) { showToast(`Macro stopped: unknown command "${step}".`, 'error'); return; }
+      await commands[step]();
+    }
+  };
+  const paletteEntries = () => [
+    ...Object.keys(commands).map(name => ({ name, run: commands[name] })),
+    ...Object.entries(macros()).map(([name, steps]) => ({ name: `Macro: ${name}`, run: () => runMacro(steps) }))
+  ];
+
+  const paletteModal = document.getElementById('command-palette-modal');
+  const paletteInput = document.getElementById('command-palette-input');
+  const paletteList = document.getElementById('command-palette-list');
+  let paletteMatches = [];
+  const hidePalette = () => {
+    modalBackdrop.classList.remove('show');
+    paletteModal.classList.remove('show');
+  };
+  const renderPalette = () => {
+    paletteMatches = paletteEntries()
+      .map(entry => ({ ...entry, score: paletteInput.value ? fuzzyScore(paletteInput.value, entry.name) : 0 }))
+      .filter(entry => entry.score >= 0)
+      .sort((a, b) => b.score - a.score);
+    paletteList.innerHTML = '';
+    paletteMatches.forEach((entry, i) => {
+      const item = document.createElement('div');
+      item.className = 'file-browser-item';
+      item.dataset.index = i;
+      item.textContent = entry.name;
+      paletteList.appendChild(item);
+    });
+  };
+  const runPaletteEntry = async (entry) => {
+    hidePalette();
+    if (recordingMacro && !entry.name.includes('Macro')) recordingMacro.push(entry.name);
+    await entry.run();
+  };
+  paletteInput.oninput = renderPalette;
+  paletteInput.onkeydown = (e) => {
+    if (e.key === 'Enter' && paletteMatches.length > 0) runPaletteEntry(paletteMatches[0]);
+    if (e.key === 'Escape') hidePalette();
+  };
+  paletteList.addEventListener('click', (e) => {
+    const item = e.target.closest('[data-index]');
+    if (item) runPaletteEntry(paletteMatches[item.dataset.index]);
+  });
+  document.addEventListener('keydown', (e) => {
+    if ((e.ctrlKey || e.metaKey) && e.shiftKey && e.key.toLowerCase() === 'p') {
+      e.preventDefault();
+      paletteInput.value = '';
+      renderPalette();
+      modalBackdrop.classList.add('show');
+      paletteModal.classList.add('show');
+      paletteInput.focus();
+    }
+  });
+
+  // Exam mode (?exam=<minutes>) starts a locked-down timed session on a fresh
+  // document: no pasting, importing or opening other documents.
+  function startExamMode(minutes) {
+    ['new-doc-btn', 'browse-local-btn', 'import-btn', 'compare-btn'].forEach(id => {
+      document.getElementById(id).style.display = 'none';
+    });
+    ['New Document', 'My Documents', 'Import File', 'Compare Two Files', 'Start Timed Session', 'End Timed Session']
+      .forEach(name => delete commands[name]);
+    ['paste', 'drop'].forEach(type => quill.root.addEventListener(type, (e) => {
+      e.preventDefault();
+      e.stopPropagation();
+      showToast('Pasting is disabled during the exam.', 'error');
+    }, true));
+    startTimedSession(minutes, true);
+  }
+
+  const tutorialCard = document.getElementById('tutorial-card');
+  let tutorialStep = null;
+  const showTutorialStep = () => {
+    const step = TUTORIAL_STEPS[tutorialStep];
+    document.getElementById('tutorial-progress').textContent = `Step ${tutorialStep + 1} of ${TUTORIAL_STEPS.length}`;
+    document.getElementById('tutorial-text').textContent = step.text;
+    tutorialCard.classList.add('show');
+  };
+  const endTutorial = () => {
+    tutorialStep = null;
+    tutorialCard.classList.remove('show');
+    localStorage.setItem(LS_TUTORIAL_DONE, 'true');
+  };
+  function startTutorial() {
+    tutorialStep = 0;
+    showTutorialStep();
+  }
+  TUTORIAL_STEPS.forEach(({ event }, index) => onDocumentEvent(event, () => {
+    if (tutorialStep !== index) return;
+    tutorialStep++;
+    if (tutorialStep < TUTORIAL_STEPS.length) {
+      showTutorialStep();
+    } else {
+      endTutorial();
+      showToast('Tutorial complete. Open it again from the command palette.', 'success');
+    }
+  }));
+  document.getElementById('tutorial-skip').onclick = endTutorial;
+  commands['Start Tutorial'] = startTutorial;
+
+  // Auditor mode (?mode=auditor) is for reviewing documents others wrote: the
+  // editor is read-only and every verification is strict. The overrides apply
+  // to this tab only and are never saved over the user's settings.
+  function startAuditorMode() {
+    auditing = true;
+    quill.disable();
+    settings = { ...settings, ...AUDITOR_SETTINGS };
+    strictToggle.checked = true;
+    strictToggle.disabled = true;
+    ['new-doc-btn', 'save-btn', 'anchor-btn', 'settings-btn'].forEach(id => {
+      document.getElementById(id).style.display = 'none';
+    });
+    ['New Document', 'Sign and Save', 'Start Timed Session', 'End Timed Session', 'Transfer Custody', 'Export Incremental Backup',
+      'Settings', 'Re-anchor', 'Add Bookmark']
+      .forEach(name => delete commands[name]);
+    document.getElementById('document-title').readOnly = true;
+    showToast('Auditor mode: documents are read-only and verified strictly.', 'info');
+  }
+
+  // Settings saved in another tab, or edited by hand, apply without a reload,
+  // so the running epoch clock and unsaved edits are kept
+  function reloadSettings() {
+    const previous = settings;
+    settings = { ...DEFAULT_SETTINGS, ...JSON.parse(localStorage.getItem(LS_SETTINGS)) };
+    if (auditing) settings = { ...settings, ...AUDITOR_SETTINGS };
+    const changed = Object.keys(settings).filter(key => settings[key] !== previous[key]);
+    changed.forEach(applySetting);
+    strictToggle.checked = settings.strictVerification;
+    if (settingsModal.classList.contains('show')) bindSettingsForm(settingsModal, applySetting);
+    return changed;
+  }
+  window.addEventListener('storage', (e) => {
+    if (e.key === LS_SETTINGS) reloadSettings();
+  });
+  commands['Reload Settings'] = () => {
+    const changed = reloadSettings();
+    showToast(changed.length > 0 ? `Reloaded settings: ${changed.join(', ')} changed.` : 'Settings are up to date.', 'info');
+  };
+
   // --- App Initialization ---
   await loadKeys();
   createNewDocument();
-  showToast('VDF calibration complete', 'success');
+  const launchParams = new URLSearchParams(window.location.search);
+  const examMinutes = Number(launchParams.get('exam'));
+  if (examMinutes > 0) startExamMode(examMinutes);
+  else if (launchParams.get('mode') === 'auditor') startAuditorMode();
+  else if (!localStorage.getItem(LS_TUTORIAL_DONE)) startTutorial();
+  if (seed !== null) showToast(`Seeded mode ("${seed}"): simulated clock, ${SEEDED_ITERATIONS} iterations per epoch.`, 'info');
+  else showToast('VDF calibration complete', 'success');
   setInterval(() => {
     if (isVDFRunning) {
       const elapsed = (Date.now() - epochStartTime) / 1000;
-      const displayProgress = currentProgress > 0 ? currentProgress : Math.min(100, (elapsed / targetSeconds) * 100);
-      updateUITimers(elapsed, targetSeconds, displayProgress);
+      const displayProgress = currentProgress > 0 ? currentProgress : Math.min(100, (elapsed / settings.epochSeconds) * 100);
+      updateUITimers(elapsed, settings.epochSeconds, displayProgress);
     }
   }, 50);
 }
@@ -0,0 +1,151 @@
+//! Stable Rust API for checking exported BitQuill documents
+//!
+//! Everything else this crate exports is the web app's wasm interface and
+//! changes with the app. The items here follow semver instead: the types are
+//! `#[non_exhaustive]` and keep their fields private, so levels and report
+//! fields can be added in minor releases. The `bitquill` tool is built on them.
+
+use super::*;
+use std::str::FromStr;
+
+/// How much of an exported document `verify_chain_report` checks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VerifyLevel {
+    /// Chain links, epoch hashes and VDF proofs
+    Chain,
+    /// Also epoch numbering, session links and the signed document hash.
+    /// The ECDSA signature itself is not checked natively.
+    Full,
+}
+
+impl FromStr for VerifyLevel {
+    type Err = String;
+
+    /// Parses the level names the `bitquill` tool accepts: "chain" or "full"
+    fn from_str(level: &str) -> Result<VerifyLevel, String> {
+        match level {
+            "chain" => Ok(VerifyLevel::Chain),
+            "full" => Ok(VerifyLevel::Full),
+            other => Err(format!("Unknown verification level: {}", other)),
+        }
+    }
+}
+
+/// Report of `verify_chain_report`, shaped like the web app's verification results.
+/// Serializes to the same JSON as `verify_chain_export` returns.
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ChainReport {
+    pub(crate) valid: bool,
+    pub(crate) errors: Vec<String>,
+    pub(crate) warnings: Vec<String>,
+    pub(crate) verified_epochs: usize,
+    pub(crate) total_epochs: usize,
+    pub(crate) signature_checked: bool,
+}
+
+impl ChainReport {
+    pub fn valid(&self) -> bool {
+        self.valid
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn verified_epochs(&self) -> usize {
+        self.verified_epochs
+    }
+
+    pub fn total_epochs(&self) -> usize {
+        self.total_epochs
+    }
+
+    /// Always false for now: no level verifies the author's ECDSA signature
+    pub fn signature_checked(&self) -> bool {
+        self.signature_checked
+    }
+}
+
+/// Verify an exported document (JSON) at the given level. Errors are documents
+/// that cannot be checked at all; findings go in the report.
+pub fn verify_chain_report(json: &str, level: VerifyLevel) -> Result<ChainReport, String> {
+    let document: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| format!("Invalid document: {}", e))?;
+    let epochs = document["proofChain"]
+        .as_array()
+        .ok_or("Document has no proof chain")?;
+    let backend = VdfBackend::for_document(&document)?;
+
+    let mut report = ChainReport {
+        total_epochs: epochs.len().saturating_sub(1),
+        errors: backend.setup_errors(),
+        ..Default::default()
+    };
+    for i in 1..epochs.len() {
+        let (epoch, previous) = (&epochs[i], &epochs[i - 1]);
+        if level == VerifyLevel::Full {
+            check_session_linkage(epoch, previous, i, &mut report);
+        }
+        if epoch["previousHash"] != previous["hash"] {
+            report.errors.push(format!("Epoch {}: Broken chain.", i));
+            continue;
+        }
+        let proof: VDFProof = match serde_json::from_value(serde_json::json!({
+            "y": epoch["vdfProof"]["y"],
+            "pi": epoch["vdfProof"]["pi"],
+            "l": epoch["vdfProof"]["l"],
+            "r": epoch["vdfProof"]["r"],
+            "iterations": epoch["iterations"],
+        })) {
+            Ok(proof) => proof,
+            Err(_) => {
+                report.errors.push(format!("Epoch {}: Malformed VDF proof.", i));
+                continue;
+            }
+        };
+        let input = epoch["previousHash"].as_str().unwrap_or_default();
+        match backend.verify_proof(input, &proof) {
+            Ok(true) => report.verified_epochs += 1,
+            Ok(false) => report.errors.push(format!("Epoch {}: Invalid VDF proof.", i)),
+            Err(e) => report.errors.push(format!("Epoch {}: Invalid VDF proof ({}).", i, e)),
+        }
+        match epoch["hashVersion"].as_u64() {
+            Some(1) => {
+                if epoch["hash"].as_str() != Some(epoch_hash(epoch).as_str()) {
+                    report.errors.push(format!("Epoch {}: Hash mismatch.", i));
+                }
+            }
+            Some(version) => report.errors.push(format!("Epoch {}: Unsupported hash version {}.", i, version)),
+            None => report.warnings.push(format!("Epoch {}: Legacy hash encoding not checked.", i)),
+        }
+        if let Some(expected) = epoch["hashSha3"].as_str() {
+            if expected != epoch_hash_sha3(epoch, previous) {
+                report.errors.push(format!("Epoch {}: SHA3 hash mismatch.", i));
+            }
+        }
+    }
+    if level == VerifyLevel::Full {
+        match document_hash(&document) {
+            Some(hash) if document["metadata"]["documentHash"].as_str() == Some(hash.as_str()) => {}
+            Some(_) => report.errors.push("Document content does not match its signed hash.".to_string()),
+            None => report.warnings.push("Legacy document hash encoding not checked.".to_string()),
+        }
+        report.warnings.push("Signature not checked: ECDSA P-384 is not available natively.".to_string());
+    }
+    let seeded_epochs = epochs.iter().filter(|epoch| epoch["seeded"] == true).count();
+    if !document["provenance"]["seeded"].is_null() || seeded_epochs > 0 {
+        report.warnings.push(format!(
+            "Seeded demo document ({} simulated epochs): its timestamps are not evidence of when it was written.",
+            seeded_epochs
+        ));
+    }
+    report.valid = report.errors.is_empty();
+    Ok(report)
+}
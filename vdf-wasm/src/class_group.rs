@@ -0,0 +1,414 @@
+//! Wesolowski's VDF in the class group of an imaginary quadratic field
+//!
+//! The RSA group is only sound while nobody knows the factorization of its modulus.
+//! The class group of a negative prime discriminant has no such trapdoor: its order
+//! is unknown to everyone, so the discriminant can be derived in public from a seed.
+//! Elements are reduced binary quadratic forms (a, b, c) with b² - 4ac = D.
+
+use super::*;
+use num_bigint::{BigInt, Sign};
+use num_traits::Signed;
+
+/// Seed of the default discriminant, see `derive_discriminant`
+#[cfg(test)]
+const DISCRIMINANT_SEED: &str = "BitQuill class group discriminant v1";
+
+/// Size of the default discriminant
+#[cfg(test)]
+const DISCRIMINANT_BITS: usize = 1024;
+
+/// -D for the default discriminant, precomputed from DISCRIMINANT_SEED
+const DEFAULT_NEGATED_DISCRIMINANT: &str = "eccb2b5da2de25e775c4fc0200e58ff3192f026613949d26fb364a66b81597f0b8d5e58791e701db284ab645d52de074fc19ac03ed15c6f79822817e228dfc24696a57b47fe2dbed67fbfaf03e6438037eff4d05451e0a19544bd4a6fec6d4eeac16ed24248c66dc5954108ebf5bc89117b7c5d8f97c6623dd3a2db952c8b057";
+
+/// Size of the primes hash_to_group draws the first coefficient from
+const HASH_TO_GROUP_BITS: usize = 128;
+
+/// D = -p for the first prime p ≡ 7 (mod 8) at or above SHA-256(seed || i) for
+/// i = 0, 1, ... with the top bit set, so anyone can check nothing was chosen
+#[cfg(test)]
+fn derive_discriminant(seed: &str, bits: usize) -> BigInt {
+    let mut bytes = Vec::new();
+    let mut counter = 0u32;
+    while bytes.len() * 8 < bits {
+        let mut hasher = Sha256::new();
+        hasher.update(seed.as_bytes());
+        hasher.update(counter.to_be_bytes());
+        bytes.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+    bytes.truncate(bits / 8);
+    let mut p = BigUint::from_bytes_be(&bytes) | (BigUint::one() << (bits - 1)) | BigUint::from(7u32);
+    while !is_probable_prime(&p, 40) {
+        p += 8u32;
+    }
+    -BigInt::from(p)
+}
+
+/// A binary quadratic form ax² + bxy + cy²
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Form {
+    a: BigInt,
+    b: BigInt,
+    c: BigInt,
+}
+
+impl Form {
+    /// The principal form (1, 1, (1 - D) / 4), the group's identity
+    fn identity(discriminant: &BigInt) -> Form {
+        Form { a: BigInt::one(), b: BigInt::one(), c: (BigInt::one() - discriminant) / 4 }
+    }
+
+    /// The form with leading coefficient a and middle coefficient b, if it has discriminant D
+    fn from_ab(a: BigInt, b: BigInt, discriminant: &BigInt) -> Option<Form> {
+        if !a.is_positive() {
+            return None;
+        }
+        let (c, remainder) = (&b * &b - discriminant).div_rem(&(&a * 4));
+        remainder.is_zero().then_some(Form { a, b, c })
+    }
+
+    /// Reduced forms are the unique representatives of their class:
+    /// |b| <= a <= c, and b >= 0 if either bound is tight
+    fn is_reduced(&self) -> bool {
+        let Form { a, b, c } = self;
+        b.abs() <= *a && a <= c && !(b.is_negative() && (b.abs() == *a || a == c))
+    }
+
+    /// Substitute x -> x + sy with s chosen to bring b into (-a, a]
+    fn normalize(&mut self) {
+        let two_a = &self.a * 2;
+        let s = (&self.a - &self.b).div_floor(&two_a);
+        self.c += &s * (&self.b + &self.a * &s);
+        self.b += &two_a * s;
+    }
+
+    /// Cohen, "A Course in Computational Algebraic Number Theory", Algorithm 5.4.2
+    fn reduce(mut self) -> Form {
+        self.normalize();
+        while self.a > self.c || (self.a == self.c && self.b.is_negative()) {
+            std::mem::swap(&mut self.a, &mut self.c);
+            self.b = -self.b;
+            self.normalize();
+        }
+        self
+    }
+
+    /// Reduced product of two forms of the same discriminant (Cohen, Algorithm 5.4.7)
+    fn compose(&self, other: &Form) -> Form {
+        let (f1, f2) = if self.a > other.a { (other, self) } else { (self, other) };
+        let s: BigInt = (&f1.b + &f2.b) / 2;
+        let n = &f2.b - &s;
+        let (y1, d) = if f2.a.is_multiple_of(&f1.a) {
+            (BigInt::zero(), f1.a.clone())
+        } else {
+            let gcd = f2.a.extended_gcd(&f1.a);
+            (gcd.x, gcd.gcd)
+        };
+        let (x2, y2, d1) = if s.is_multiple_of(&d) {
+            (BigInt::zero(), -BigInt::one(), d)
+        } else {
+            let gcd = s.extended_gcd(&d);
+            (gcd.x, -gcd.y, gcd.gcd)
+        };
+        let v1 = &f1.a / &d1;
+        let v2 = &f2.a / &d1;
+        let r = (&y1 * &y2 * &n - &x2 * &f2.c).mod_floor(&v1);
+        let b3 = &f2.b + &v2 * &r * 2;
+        let c3 = (&f2.c * &d1 + &r * (&f2.b + &v2 * &r)) / &v1;
+        Form { a: v1 * v2, b: b3, c: c3 }.reduce()
+    }
+
+    fn square(&self) -> Form {
+        self.compose(self)
+    }
+
+    fn pow(&self, exponent: &BigUint, discriminant: &BigInt) -> Form {
+        let mut result = Form::identity(discriminant);
+        for i in (0..exponent.bits()).rev() {
+            result = result.square();
+            if exponent.bit(i) {
+                result = result.compose(self);
+            }
+        }
+        result
+    }
+
+    /// "<a>:<b>" with both in base64 big-endian and b prefixed with '-' when negative
+    fn encode(&self) -> String {
+        let (sign, b) = self.b.to_bytes_be();
+        format!(
+            "{}:{}{}",
+            general_purpose::STANDARD.encode(self.a.to_bytes_be().1),
+            if sign == Sign::Minus { "-" } else { "" },
+            general_purpose::STANDARD.encode(b)
+        )
+    }
+
+    /// Inverse of `encode`, accepting only reduced forms of this discriminant
+    fn decode(field: &'static str, encoded: &str, discriminant: &BigInt) -> Result<Form, VdfError> {
+        let malformed = |reason: &str| VdfError::Decode { field, reason: reason.to_string() };
+        let (a, b) = encoded.split_once(':').ok_or_else(|| malformed("Expected a form \"a:b\""))?;
+        let (negative, b) = match b.strip_prefix('-') {
+            Some(b) => (true, b),
+            None => (false, b),
+        };
+        let a = BigInt::from(base64_to_biguint(field, a)?);
+        let b = BigInt::from(base64_to_biguint(field, b)?);
+        let form = Form::from_ab(a, if negative { -b } else { b }, discriminant)
+            .ok_or_else(|| malformed("Not a form of the class group discriminant"))?;
+        if !form.is_reduced() {
+            return Err(malformed("Form is not reduced"));
+        }
+        Ok(form)
+    }
+}
+
+/// VDF computer over the class group of a public, seed-derived discriminant
+#[wasm_bindgen]
+pub struct ClassGroupVDF {
+    discriminant: BigInt,
+}
+
+#[wasm_bindgen]
+impl ClassGroupVDF {
+    /// Create a VDF computer over the default 1024-bit discriminant
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ClassGroupVDF {
+        let p = BigUint::parse_bytes(DEFAULT_NEGATED_DISCRIMINANT.as_bytes(), 16)
+            .expect("Failed to parse discriminant");
+        ClassGroupVDF { discriminant: -BigInt::from(p) }
+    }
+
+    /// Compute a VDF proof with progress callback
+    #[wasm_bindgen]
+    pub fn compute_proof(
+        &self,
+        input: &str,
+        iterations: u64,
+        on_progress: Option<Function>,
+    ) -> Result<VDFProof, JsValue> {
+        Ok(self.compute_proof_internal(input, iterations, on_progress)?)
+    }
+
+    /// Verify a VDF proof
+    #[wasm_bindgen]
+    pub fn verify_proof(&self, input: &str, proof: &VDFProof) -> Result<bool, JsValue> {
+        Ok(self.verify_proof_internal(input, proof)?)
+    }
+
+    /// SHA-256 fingerprint (hex) of -D, identifying the group proofs are computed in
+    #[wasm_bindgen]
+    pub fn discriminant_fingerprint(&self) -> String {
+        Sha256::digest(self.discriminant.magnitude().to_bytes_be())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+impl Default for ClassGroupVDF {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClassGroupVDF {
+    fn compute_proof_internal(
+        &self,
+        input: &str,
+        iterations: u64,
+        on_progress: Option<Function>,
+    ) -> Result<VDFProof, VdfError> {
+        if !(MIN_ITERATIONS..=MAX_ITERATIONS).contains(&iterations) {
+            return Err(VdfError::IterationsOutOfRange(iterations));
+        }
+        if input.is_empty() {
+            return Err(VdfError::EmptyInput);
+        }
+
+        let x = self.hash_to_group(input)?;
+        let mut y = x.clone();
+        let mut last_progress = 0u64;
+        for i in 0..iterations {
+            y = y.square();
+            report_progress(&on_progress, i, iterations, &mut last_progress);
+        }
+        let l = self.fiat_shamir_prime(&x, &y, iterations)?;
+        let r = BigUint::from(2u32).modpow(&BigUint::from(iterations), &l);
+        let pi = self.wesolowski_proof(&x, iterations, &l);
+
+        let proof = VDFProof {
+            y: y.encode(),
+            pi: pi.encode(),
+            l: general_purpose::STANDARD.encode(l.to_bytes_be()),
+            r: general_purpose::STANDARD.encode(r.to_bytes_be()),
+            iterations,
+            timestamp: js_sys::Date::now() as u64,
+        };
+        if !self.verify_proof_internal(input, &proof)? {
+            error_log!("Self-verification failed for {} iterations", iterations);
+            return Err(VdfError::SelfVerification { iterations });
+        }
+        Ok(proof)
+    }
+
+    pub(crate) fn verify_proof_internal(&self, input: &str, proof: &VDFProof) -> Result<bool, VdfError> {
+        if !(MIN_ITERATIONS..=MAX_ITERATIONS).contains(&proof.iterations) {
+            return Ok(false);
+        }
+        let y = Form::decode("y", &proof.y, &self.discriminant)?;
+        let pi = Form::decode("pi", &proof.pi, &self.discriminant)?;
+        let l = base64_to_biguint("l", &proof.l)?;
+        let r = base64_to_biguint("r", &proof.r)?;
+
+        let x = self.hash_to_group(input)?;
+        if l != self.fiat_shamir_prime(&x, &y, proof.iterations)? {
+            debug_log!("Challenge prime mismatch");
+            return Ok(false);
+        }
+        if r != BigUint::from(2u32).modpow(&BigUint::from(proof.iterations), &l) {
+            debug_log!("Remainder mismatch");
+            return Ok(false);
+        }
+        // y = π^l · x^r
+        Ok(pi.pow(&l, &self.discriminant).compose(&x.pow(&r, &self.discriminant)) == y)
+    }
+
+    /// Map the input to the form (a, b, c) for the first prime a ≡ 3 (mod 4) drawn
+    /// from its hash with D a square mod a, taking b = √D mod a of D's parity
+    fn hash_to_group(&self, input: &str) -> Result<Form, VdfError> {
+        let p = self.discriminant.magnitude();
+        for counter in 0..10_000u32 {
+            let mut hasher = Sha256::new();
+            hasher.update(b"VDF_CLASS_GROUP_HASH_TO_GROUP_v1");
+            hasher.update(input.as_bytes());
+            hasher.update(p.to_bytes_be());
+            hasher.update(counter.to_be_bytes());
+            let hash = hasher.finalize();
+            let a = BigUint::from_bytes_be(&hash[..HASH_TO_GROUP_BITS / 8])
+                | (BigUint::one() << (HASH_TO_GROUP_BITS - 1))
+                | BigUint::from(3u32);
+            if !is_probable_prime(&a, 40) {
+                continue;
+            }
+            let d_mod_a = (&a - p % &a) % &a;
+            // Euler's criterion; D is a square mod a iff D^((a-1)/2) = 1
+            if d_mod_a.modpow(&((&a - 1u32) >> 1), &a) != BigUint::one() {
+                continue;
+            }
+            let mut b = d_mod_a.modpow(&((&a + 1u32) >> 2), &a);
+            if b.is_even() {
+                b = &a - b;
+            }
+            if let Some(form) = Form::from_ab(BigInt::from(a), BigInt::from(b), &self.discriminant) {
+                return Ok(form.reduce());
+            }
+        }
+        Err(VdfError::HashToGroup)
+    }
+
+    fn fiat_shamir_prime(&self, x: &Form, y: &Form, iterations: u64) -> Result<BigUint, VdfError> {
+        let mut hasher = Sha256::new();
+        hasher.update(b"VDF_FIAT_SHAMIR_CLASS_GROUP_v1");
+        hasher.update(x.encode().as_bytes());
+        hasher.update(y.encode().as_bytes());
+        hasher.update(iterations.to_be_bytes());
+        hasher.update(self.discriminant.magnitude().to_bytes_be());
+        hash_to_prime(&hasher)
+    }
+
+    /// π = x^⌊2^t / l⌋, computed bit by bit by long division of 2^t by l
+    fn wesolowski_proof(&self, x: &Form, iterations: u64, l: &BigUint) -> Form {
+        let mut pi = Form::identity(&self.discriminant);
+        let mut remainder = BigUint::zero();
+        for i in (0..=iterations).rev() {
+            pi = pi.square();
+            remainder <<= 1;
+            if i == iterations {
+                remainder |= BigUint::one();
+            }
+            if remainder >= *l {
+                remainder -= l;
+                pi = pi.compose(x);
+            }
+        }
+        pi
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))] // wasm_bindgen_test only runs under wasm-pack
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    /// Reduced forms of a small discriminant, all of them if D = -23, -47 or -71
+    fn forms_of(discriminant: i64) -> Vec<Form> {
+        let d = BigInt::from(discriminant);
+        let mut forms = Vec::new();
+        for a in 1..=((-discriminant / 3) as f64).sqrt() as i64 + 1 {
+            for b in -a..=a {
+                if let Some(form) = Form::from_ab(BigInt::from(a), BigInt::from(b), &d) {
+                    if form.is_reduced() {
+                        forms.push(form);
+                    }
+                }
+            }
+        }
+        forms
+    }
+
+    #[wasm_bindgen_test]
+    fn test_small_class_groups_have_known_orders() {
+        for (discriminant, class_number) in [(-23i64, 3u32), (-47, 5), (-71, 7)] {
+            let d = BigInt::from(discriminant);
+            let forms = forms_of(discriminant);
+            assert_eq!(forms.len() as u32, class_number, "h({})", discriminant);
+            for form in &forms {
+                assert_eq!(form.pow(&BigUint::from(class_number), &d), Form::identity(&d));
+                for other in &forms {
+                    assert_eq!(form.compose(other), other.compose(form));
+                }
+            }
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_default_discriminant_is_derived_from_seed() {
+        let vdf = ClassGroupVDF::new();
+        assert_eq!(derive_discriminant(DISCRIMINANT_SEED, DISCRIMINANT_BITS), vdf.discriminant);
+        assert_eq!(vdf.discriminant.bits(), DISCRIMINANT_BITS as u64);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_composition_laws() {
+        let vdf = ClassGroupVDF::new();
+        let d = &vdf.discriminant;
+        let f = vdf.hash_to_group("first").unwrap();
+        let g = vdf.hash_to_group("second").unwrap();
+        let h = vdf.hash_to_group("third").unwrap();
+        assert!(f.is_reduced());
+        assert_eq!(f.compose(&Form::identity(d)), f);
+        assert_eq!(f.compose(&g).compose(&h), f.compose(&g.compose(&h)));
+        let inverse = Form { a: f.a.clone(), b: -f.b.clone(), c: f.c.clone() }.reduce();
+        assert_eq!(f.compose(&inverse), Form::identity(d));
+        let (m, n) = (BigUint::from(1234u32), BigUint::from(5678u32));
+        assert_eq!(f.pow(&m, d).compose(&f.pow(&n, d)), f.pow(&(&m + &n), d));
+        assert_eq!(Form::decode("y", &f.encode(), d).unwrap(), f);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_class_group_proof_round_trip() {
+        let vdf = ClassGroupVDF::new();
+        let input = "class group proof test";
+        let proof = vdf.compute_proof(input, MIN_ITERATIONS, None).unwrap();
+        assert!(vdf.verify_proof(input, &proof).unwrap());
+        assert!(!vdf.verify_proof("another input", &proof).unwrap());
+
+        let mut tampered = proof.clone();
+        tampered.y = vdf.hash_to_group("not the output").unwrap().encode();
+        assert!(!vdf.verify_proof(input, &tampered).unwrap());
+    }
+}
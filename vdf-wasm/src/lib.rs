@@ -9,10 +9,13 @@ use num_bigint::{BigUint, RandBigInt};
 use num_traits::{Zero, One};
 use num_integer::Integer;
 use sha2::{Sha256, Digest};
+use sha3::Sha3_256;
 use rand::thread_rng;
 use base64::{Engine as _, engine::general_purpose};
 use js_sys::Function;
 use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
 
 /// RSA-2048 modulus from the RSA Factoring Challenge
 /// This modulus has unknown factorization, making it suitable for VDF
@@ -30,6 +33,83 @@ const MIN_ITERATIONS: u64 = 1000;
 /// Progress reporting interval
 const PROGRESS_INTERVAL: u64 = 10000;
 
+/// Minimum modulus size accepted for custom moduli
+const MIN_MODULUS_BITS: u64 = 1024;
+
+/// Trial division bound when checking a custom modulus for small factors
+const TRIAL_DIVISION_LIMIT: usize = 10_000;
+
+/// Steps of Fermat's method tried against a custom modulus (catches p and q too close together)
+const FERMAT_ROUNDS: u32 = 1000;
+
+/// 512-bit RSA modulus generated with OpenSSL; fast, but its factors are not destroyed
+const TEST_512_MODULUS: &str = "bc975c587f80c63fc038828ed7416a2c0cf209e434494b77096086f47cbafff224d6c853998f3cfb8a8fd1c847b06666561e8ef5adfe5b3e11c09ac7324c4119";
+
+/// A named modulus that documents can reference by ID
+#[derive(Serialize)]
+struct ModulusProfile {
+    id: &'static str,
+    name: &'static str,
+    /// "trusted" for production use, "test" for moduli that must never back real documents
+    trust: &'static str,
+    #[serde(skip)]
+    modulus_hex: &'static str,
+}
+
+/// Built-in modulus registry
+const MODULUS_PROFILES: &[ModulusProfile] = &[
+    ModulusProfile { id: "rsa-2048", name: "RSA-2048 Factoring Challenge", trust: "trusted", modulus_hex: RSA_2048_MODULUS },
+    ModulusProfile { id: "test-512", name: "512-bit test modulus", trust: "test", modulus_hex: TEST_512_MODULUS },
+];
+
+/// Moduli whose factorization is public; VDF outputs in these groups can be computed instantly
+const KNOWN_FACTORED_MODULI: &[(&str, &str)] = &[
+    ("RSA-100", "2C8D59AF47C81AB3725B472BE417E3BF7AB85439AF726ED3DFDF66489D155DC0B771C7A50EF7C5E58FB"),
+    ("RSA-110", "F3DB4DFACD9CA1D1C77CDA2C23E8826C929130886E2FFFA6E21271CA6F3E2A5A57DE621EEC5B14FF581A6368E9B"),
+    ("RSA-120", "168164DBDDB07AFE12E5BE8CABECFC694EFF9643D51F533915EE55B217697CC78256503276EE00AF4DFE166C0725FDC1447F"),
+    ("RSA-129", "2A3E4A7E967464D174F174C28251D97BD375C607ACE8FAE415630B45733C2259D2AFC68DD6F447AC5BAFB686CA5A4DC6245D5E2E8F5"),
+];
+
+/// Why a proof could not be computed or checked. Crosses into JS as its message.
+#[derive(Debug, Clone, PartialEq)]
+enum VdfError {
+    IterationsOutOfRange(u64),
+    EmptyInput,
+    HashToGroup,
+    ChallengePrime,
+    /// A freshly computed proof failed its own check
+    SelfVerification { iterations: u64 },
+    /// A proof field that is not valid, non-empty base64
+    Decode { field: &'static str, reason: String },
+}
+
+impl fmt::Display for VdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VdfError::IterationsOutOfRange(iterations) => write!(
+                f,
+                "Iterations must be between {} and {} (got {})",
+                MIN_ITERATIONS, MAX_ITERATIONS, iterations
+            ),
+            VdfError::EmptyInput => write!(f, "Input cannot be empty"),
+            VdfError::HashToGroup => write!(f, "Failed to hash to group"),
+            VdfError::ChallengePrime => write!(f, "Failed to generate challenge prime"),
+            VdfError::SelfVerification { iterations } => {
+                write!(f, "Self-verification failed for {} iterations", iterations)
+            }
+            VdfError::Decode { field, reason } => write!(f, "Proof field {}: {}", field, reason),
+        }
+    }
+}
+
+impl std::error::Error for VdfError {}
+
+impl From<VdfError> for JsValue {
+    fn from(error: VdfError) -> JsValue {
+        JsValue::from_str(&error.to_string())
+    }
+}
+
 // Enable console logging for debugging
 #[wasm_bindgen]
 extern "C" {
@@ -43,16 +123,52 @@ extern "C" {
     fn warn(s: &str);
 }
 
+/// Log levels, most severe first; messages above the current level are dropped
+const LOG_ERROR: u8 = 0;
+const LOG_WARN: u8 = 1;
+const LOG_DEBUG: u8 = 2;
+
+/// Current log level, warnings by default so proof internals stay out of the console
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LOG_WARN);
+
+fn log_enabled(level: u8) -> bool {
+    level <= LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Set the log level: "error", "warn" or "debug"
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) -> Result<(), JsValue> {
+    let value = match level {
+        "error" => LOG_ERROR,
+        "warn" => LOG_WARN,
+        "debug" => LOG_DEBUG,
+        _ => return Err(JsValue::from_str(&format!("Unknown log level: {}", level))),
+    };
+    LOG_LEVEL.store(value, Ordering::Relaxed);
+    Ok(())
+}
+
 /// Macro for debug logging
 macro_rules! debug_log {
-    ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
+    ($($t:tt)*) => (if log_enabled(LOG_DEBUG) { log(&format_args!($($t)*).to_string()) })
 }
 
 /// Macro for error logging
 macro_rules! error_log {
-    ($($t:tt)*) => (error(&format_args!($($t)*).to_string()))
+    ($($t:tt)*) => (if log_enabled(LOG_ERROR) { error(&format_args!($($t)*).to_string()) })
 }
 
+/// Macro for warning logging
+macro_rules! warn_log {
+    ($($t:tt)*) => (if log_enabled(LOG_WARN) { warn(&format_args!($($t)*).to_string()) })
+}
+
+mod class_group;
+pub use class_group::ClassGroupVDF;
+
+pub mod api;
+use api::{verify_chain_report, ChainReport, VerifyLevel};
+
 /// VDF Proof structure containing all verification parameters
 #[wasm_bindgen]
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -74,6 +190,7 @@ pub struct VDFProof {
     
     /// Proof generation timestamp (for audit trail)
     #[serde(skip)]
+    #[allow(dead_code)]
     timestamp: u64,
 }
 
@@ -131,13 +248,131 @@ impl VDFProof {
     }
 }
 
+/// Limbs are machine words so a limb product fits the native double-width
+/// multiply; wasm32 has no 64x64->128 bit multiply.
+#[cfg(target_pointer_width = "64")]
+type Limb = u64;
+#[cfg(target_pointer_width = "64")]
+type WideLimb = u128;
+#[cfg(not(target_pointer_width = "64"))]
+type Limb = u32;
+#[cfg(not(target_pointer_width = "64"))]
+type WideLimb = u64;
+
+/// Montgomery arithmetic modulo an odd N on fixed limbs (least significant first).
+/// Values stay in Montgomery form a·R mod N, with R = 2^(Limb::BITS·limbs), so
+/// each product is reduced with REDC instead of a long division by N.
+struct Montgomery {
+    n: Vec<Limb>,
+    /// -N^-1 mod 2^Limb::BITS
+    n_prime: Limb,
+    /// R mod N, i.e. 1 in Montgomery form
+    one: Vec<Limb>,
+}
+
+impl Montgomery {
+    fn new(modulus: &BigUint) -> Montgomery {
+        let n = Montgomery::limbs(modulus);
+        // Newton's iteration doubles the correct low bits of N^-1 each step
+        let mut inverse: Limb = 1;
+        for _ in 0..Limb::BITS.trailing_zeros() {
+            inverse = inverse.wrapping_mul((2 as Limb).wrapping_sub(n[0].wrapping_mul(inverse)));
+        }
+        let mut montgomery = Montgomery { n, n_prime: inverse.wrapping_neg(), one: Vec::new() };
+        montgomery.one = montgomery.encode(&BigUint::one());
+        montgomery
+    }
+
+    /// value·R mod N
+    fn encode(&self, value: &BigUint) -> Vec<Limb> {
+        let shift = Limb::BITS as usize * self.n.len();
+        let mut limbs = Montgomery::limbs(&((value << shift) % Montgomery::biguint(&self.n)));
+        limbs.resize(self.n.len(), 0);
+        limbs
+    }
+
+    /// value·R^-1 mod N, leaving Montgomery form
+    fn decode(&self, value: &[Limb]) -> BigUint {
+        let mut unit = vec![0; self.n.len()];
+        unit[0] = 1;
+        let mut out = vec![0; self.n.len()];
+        self.mul(value, &unit, &mut out, &mut vec![0; self.n.len() + 1]);
+        Montgomery::biguint(&out)
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    fn limbs(value: &BigUint) -> Vec<Limb> {
+        value.to_u64_digits()
+    }
+
+    #[cfg(not(target_pointer_width = "64"))]
+    fn limbs(value: &BigUint) -> Vec<Limb> {
+        value.to_u32_digits()
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    fn biguint(limbs: &[Limb]) -> BigUint {
+        BigUint::new(limbs.iter().flat_map(|&limb| [limb as u32, (limb >> 32) as u32]).collect())
+    }
+
+    #[cfg(not(target_pointer_width = "64"))]
+    fn biguint(limbs: &[Limb]) -> BigUint {
+        BigUint::from_slice(limbs)
+    }
+
+    /// out = a·b·R^-1 mod N (CIOS, with the multiply and reduce passes fused).
+    /// `scratch` needs limbs + 1 words.
+    fn mul(&self, a: &[Limb], b: &[Limb], out: &mut [Limb], scratch: &mut [Limb]) {
+        let k = self.n.len();
+        let t = &mut scratch[..k + 1];
+        let (a, b, n) = (&a[..k], &b[..k], &self.n[..k]);
+        t.fill(0);
+        for &a_i in a {
+            // t = (t + a_i·b + m·N) / 2^Limb::BITS, with m chosen so the low limb cancels
+            let sum = t[0] as WideLimb + a_i as WideLimb * b[0] as WideLimb;
+            let m = (sum as Limb).wrapping_mul(self.n_prime);
+            let mut carry = (sum >> Limb::BITS) as Limb;
+            let mut reduce_carry = (((sum as Limb) as WideLimb + m as WideLimb * n[0] as WideLimb) >> Limb::BITS) as Limb;
+            for j in 1..k {
+                let sum = t[j] as WideLimb + a_i as WideLimb * b[j] as WideLimb + carry as WideLimb;
+                carry = (sum >> Limb::BITS) as Limb;
+                let reduced = (sum as Limb) as WideLimb + m as WideLimb * n[j] as WideLimb + reduce_carry as WideLimb;
+                reduce_carry = (reduced >> Limb::BITS) as Limb;
+                t[j - 1] = reduced as Limb;
+            }
+            let sum = t[k] as WideLimb + carry as WideLimb + reduce_carry as WideLimb;
+            t[k - 1] = sum as Limb;
+            t[k] = (sum >> Limb::BITS) as Limb;
+        }
+        // t < 2N here, so at most one subtraction brings it below N
+        if t[k] != 0 || !less_than(&t[..k], &self.n) {
+            let mut borrow = false;
+            for (t_j, &n_j) in t.iter_mut().zip(n) {
+                let (diff, b1) = t_j.overflowing_sub(n_j);
+                let (diff, b2) = diff.overflowing_sub(borrow as Limb);
+                *t_j = diff;
+                borrow = b1 || b2;
+            }
+        }
+        out.copy_from_slice(&t[..k]);
+    }
+}
+
+fn less_than(a: &[Limb], b: &[Limb]) -> bool {
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x < y;
+        }
+    }
+    false
+}
+
 /// Main VDF computer with optimized algorithms
 #[wasm_bindgen]
 pub struct VDFComputer {
     modulus: BigUint,
-    /// Precomputed Montgomery parameters for faster modular arithmetic
-    montgomery_r: BigUint,
-    montgomery_r_inv: BigUint,
+    /// Montgomery form of the modulus for the squaring loops
+    montgomery: Montgomery,
 }
 
 #[wasm_bindgen]
@@ -148,14 +383,13 @@ impl VDFComputer {
         let modulus = BigUint::parse_bytes(RSA_2048_MODULUS.as_bytes(), 16)
             .expect("Failed to parse modulus");
         
-        let montgomery_r = BigUint::one() << modulus.bits();
-        let montgomery_r_inv = montgomery_r.clone();
-        
-        VDFComputer {
-            modulus,
-            montgomery_r,
-            montgomery_r_inv,
-        }
+        VDFComputer::from_modulus(modulus)
+    }
+    
+    /// Create a VDF computer for a registered modulus profile (see `modulus_profiles`)
+    #[wasm_bindgen]
+    pub fn with_profile(profile_id: &str) -> Result<VDFComputer, JsValue> {
+        Ok(VDFComputer::from_profile(profile_id)?)
     }
     
     /// Create a VDF computer with a custom modulus (hex string)
@@ -164,20 +398,12 @@ impl VDFComputer {
         let modulus = BigUint::parse_bytes(modulus_hex.as_bytes(), 16)
             .ok_or_else(|| JsValue::from_str("Invalid modulus format"))?;
         
-        // Validate modulus is odd and large enough
-        if modulus.is_even() || modulus.bits() < 1024 {
-            return Err(JsValue::from_str("Modulus must be odd and at least 1024 bits"));
+        let weaknesses = modulus_weaknesses(&modulus);
+        if !weaknesses.is_empty() {
+            return Err(JsValue::from_str(&weaknesses.join("; ")));
         }
         
-        // Precompute Montgomery parameters (simplified for this example)
-        let montgomery_r = BigUint::one() << modulus.bits();
-        let montgomery_r_inv = montgomery_r.clone();
-        
-        Ok(VDFComputer {
-            modulus,
-            montgomery_r,
-            montgomery_r_inv,
-        })
+        Ok(VDFComputer::from_modulus(modulus))
     }
     
     /// Compute a VDF proof with progress callback
@@ -188,15 +414,63 @@ impl VDFComputer {
         iterations: u64,  // wasm-bindgen handles BigInt -> u64 conversion
         on_progress: Option<Function>,  
     ) -> Result<VDFProof, JsValue> {
-        self.compute_proof_internal(input, iterations, on_progress)
-            .map_err(|e| JsValue::from_str(&e))
+        Ok(self.compute_proof_internal(input, iterations, on_progress)?)
     }
     
     /// Verify a VDF proof
     #[wasm_bindgen]
     pub fn verify_proof(&self, input: &str, proof: &VDFProof) -> Result<bool, JsValue> {
-        self.verify_proof_internal(input, proof)
-            .map_err(|e| JsValue::from_str(&e))
+        Ok(self.verify_proof_internal(input, proof)?)
+    }
+    
+    /// Every intermediate value of verifying a proof, as JSON, for showing the math
+    /// behind an epoch. Recomputes what verify_proof does and reports instead of deciding.
+    #[wasm_bindgen]
+    pub fn explain_proof(&self, input: &str, proof: &VDFProof) -> Result<String, JsValue> {
+        let y = base64_to_biguint("y", &proof.y)?;
+        let pi = base64_to_biguint("pi", &proof.pi)?;
+        let l = base64_to_biguint("l", &proof.l)?;
+        let r = base64_to_biguint("r", &proof.r)?;
+        let x = self.hash_to_group(input)?;
+        let expected_l = self.generate_fiat_shamir_prime(&x, &y, proof.iterations)?;
+        let pi_l = pi.modpow(&l, &self.modulus);
+        let x_r = x.modpow(&r, &self.modulus);
+        let product = (&pi_l * &x_r) % &self.modulus;
+        let explanation = ProofExplanation {
+            modulus_fingerprint: self.modulus_fingerprint(),
+            modulus_bits: self.modulus.bits(),
+            iterations: proof.iterations,
+            input: input.to_string(),
+            x: x.to_str_radix(16),
+            y: y.to_str_radix(16),
+            pi: pi.to_str_radix(16),
+            l: l.to_str_radix(16),
+            r: r.to_str_radix(16),
+            l_is_prime: is_probable_prime(&l, 20),
+            l_matches_fiat_shamir: l == expected_l,
+            expected_l: expected_l.to_str_radix(16),
+            r_matches: r == BigUint::from(2u32).modpow(&BigUint::from(proof.iterations), &l),
+            pi_l: pi_l.to_str_radix(16),
+            x_r: x_r.to_str_radix(16),
+            product: product.to_str_radix(16),
+            holds: y == product,
+        };
+        serde_json::to_string(&explanation).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    
+    /// SHA-256 fingerprint (hex) of the modulus, identifying the group proofs are computed in
+    #[wasm_bindgen]
+    pub fn modulus_fingerprint(&self) -> String {
+        Sha256::digest(self.modulus.to_bytes_be())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+    
+    /// Reasons this computer's modulus is unsafe for a VDF (empty if none were found)
+    #[wasm_bindgen]
+    pub fn modulus_weaknesses(&self) -> Vec<String> {
+        modulus_weaknesses(&self.modulus)
     }
     
     /// Estimate iterations needed for a given time in seconds
@@ -209,23 +483,42 @@ impl VDFComputer {
         iterations.clamp(MIN_ITERATIONS, MAX_ITERATIONS)
     }
     
+    /// Build a computer for a registered modulus profile, without JS types
+    fn from_profile(profile_id: &str) -> Result<VDFComputer, String> {
+        let profile = MODULUS_PROFILES
+            .iter()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| format!("Unknown modulus profile: {}", profile_id))?;
+        let modulus = BigUint::parse_bytes(profile.modulus_hex.as_bytes(), 16)
+            .expect("Failed to parse modulus");
+        
+        Ok(VDFComputer::from_modulus(modulus))
+    }
+    
+    /// Build a computer around an already validated modulus
+    fn from_modulus(modulus: BigUint) -> VDFComputer {
+        let montgomery = Montgomery::new(&modulus);
+        
+        VDFComputer {
+            modulus,
+            montgomery,
+        }
+    }
+    
     /// Internal proof generation with full error handling
     fn compute_proof_internal(
         &self,
         input: &str,
         iterations: u64,
         on_progress: Option<Function>,
-    ) -> Result<VDFProof, String> {
+    ) -> Result<VDFProof, VdfError> {
         // Validate parameters
-        if iterations < MIN_ITERATIONS || iterations > MAX_ITERATIONS {
-            return Err(format!(
-                "Iterations must be between {} and {}",
-                MIN_ITERATIONS, MAX_ITERATIONS
-            ));
+        if !(MIN_ITERATIONS..=MAX_ITERATIONS).contains(&iterations) {
+            return Err(VdfError::IterationsOutOfRange(iterations));
         }
         
         if input.is_empty() {
-            return Err("Input cannot be empty".to_string());
+            return Err(VdfError::EmptyInput);
         }
         
         debug_log!("Starting VDF computation with {} iterations", iterations);
@@ -276,23 +569,24 @@ impl VDFComputer {
         
         // Self-verify as sanity check
         if !self.verify_proof_internal(input, &proof)? {
-            return Err("Self-verification failed".to_string());
+            error_log!("Self-verification failed for {} iterations", iterations);
+            return Err(VdfError::SelfVerification { iterations });
         }
         
         Ok(proof)
     }
     
     /// Hash input to a group element
-    fn hash_to_group(&self, input: &str) -> Result<BigUint, String> {
+    fn hash_to_group(&self, input: &str) -> Result<BigUint, VdfError> {
         let mut hasher = Sha256::new();
         hasher.update(b"VDF_HASH_TO_GROUP_v1");
         hasher.update(input.as_bytes());
-        hasher.update(&self.modulus.to_bytes_be());
+        hasher.update(self.modulus.to_bytes_be());
         
         let mut counter = 0u32;
         loop {
             let mut h = hasher.clone();
-            h.update(&counter.to_be_bytes());
+            h.update(counter.to_be_bytes());
             let hash = h.finalize();
             let candidate = BigUint::from_bytes_be(&hash);
             
@@ -306,7 +600,7 @@ impl VDFComputer {
             
             counter += 1;
             if counter > 1000 {
-                return Err("Failed to hash to group".to_string());
+                return Err(VdfError::HashToGroup);
             }
         }
     }
@@ -317,37 +611,22 @@ impl VDFComputer {
         x: &BigUint,
         iterations: u64,
         on_progress: &Option<Function>,
-    ) -> Result<BigUint, String> {
-        let mut y = x.clone();
+    ) -> Result<BigUint, VdfError> {
+        let mont = &self.montgomery;
+        let mut y = mont.encode(x);
+        let mut squared = y.clone();
+        let mut scratch = vec![0; y.len() + 1];
         let mut last_progress = 0u64;
         
         for i in 0..iterations {
-            // Optimized squaring: y = y^2 mod N
-            y = self.mod_square(&y);
+            // y = y^2 mod N, in Montgomery form
+            mont.mul(&y, &y, &mut squared, &mut scratch);
+            std::mem::swap(&mut y, &mut squared);
             
-            // Progress reporting
-            if let Some(callback) = on_progress {
-                if i % PROGRESS_INTERVAL == 0 || i == iterations - 1 {
-                    let progress = ((i + 1) * 100) / iterations;
-                    if progress != last_progress {
-                        last_progress = progress;
-                        let this = JsValue::null();
-                        let progress_val = JsValue::from_f64(progress as f64);
-                        if let Err(e) = callback.call1(&this, &progress_val) {
-                            warn(&format!("Progress callback error: {:?}", e));
-                        }
-                    }
-                }
-            }
+            report_progress(on_progress, i, iterations, &mut last_progress);
         }
         
-        Ok(y)
-    }
-    
-    /// Optimized modular squaring
-    fn mod_square(&self, x: &BigUint) -> BigUint {
-        // For production, implement Montgomery multiplication
-        (x * x) % &self.modulus
+        Ok(mont.decode(&y))
     }
     
     /// Generate deterministic challenge prime using Fiat-Shamir
@@ -356,56 +635,18 @@ impl VDFComputer {
         x: &BigUint,
         y: &BigUint,
         iterations: u64,
-    ) -> Result<BigUint, String> {
+    ) -> Result<BigUint, VdfError> {
         let mut hasher = Sha256::new();
         hasher.update(b"VDF_FIAT_SHAMIR_v1");
-        hasher.update(&x.to_bytes_be());
-        hasher.update(&y.to_bytes_be());
-        hasher.update(&iterations.to_be_bytes());
-        hasher.update(&self.modulus.to_bytes_be());
-        
-       
-        // Use the hash directly for deterministic generation
-        for attempt in 0..1000 {
-            let mut h = hasher.clone();  // Clone the original hasher
-            h.update(&(attempt as u32).to_be_bytes());
-            // Don't finalize h here either - we need it for the inner loop
-            
-            // Build a SECURITY_BITS sized number from repeated hashing
-            let mut bytes = Vec::new();
-            let mut counter = 0u32;
-            while bytes.len() * 8 < SECURITY_BITS {
-                let mut h2 = h.clone();  // Clone h each time
-                h2.update(&counter.to_be_bytes());
-                bytes.extend_from_slice(&h2.finalize());  // Only finalize h2
-                counter += 1;
-            }
-            
-            // Truncate to exact bit length
-            let bytes_needed = (SECURITY_BITS + 7) / 8;
-            bytes.truncate(bytes_needed);
-            
-            let mut candidate = BigUint::from_bytes_be(&bytes);
-            
-            // Ensure exactly SECURITY_BITS
-            if SECURITY_BITS % 8 != 0 {
-                candidate >>= 8 - (SECURITY_BITS % 8);
-            }
-            
-            candidate |= BigUint::one(); // Make odd
-            candidate |= BigUint::one() << (SECURITY_BITS - 1); // Set high bit
-            
-            if self.is_probable_prime(&candidate, 40) {
-                debug_log!("Generated challenge prime in {} attempts", attempt + 1);
-                return Ok(candidate);
-            }
-        }
-        
-        Err("Failed to generate challenge prime".to_string())
+        hasher.update(x.to_bytes_be());
+        hasher.update(y.to_bytes_be());
+        hasher.update(iterations.to_be_bytes());
+        hasher.update(self.modulus.to_bytes_be());
+        hash_to_prime(&hasher)
     }
     
     /// Compute r = 2^t mod l efficiently
-    fn compute_remainder(&self, iterations: u64, l: &BigUint) -> Result<BigUint, String> {
+    fn compute_remainder(&self, iterations: u64, l: &BigUint) -> Result<BigUint, VdfError> {
         // Use binary exponentiation
         let base = BigUint::from(2u32);
         Ok(base.modpow(&BigUint::from(iterations), l))
@@ -417,11 +658,15 @@ fn compute_wesolowski_proof(
     x: &BigUint,
     iterations: u64,
     l: &BigUint,
-) -> Result<BigUint, String> {
+) -> Result<BigUint, VdfError> {
     // We compute pi = x^q, where q is the quotient of 2^t / l.
     // The bits of q are determined by a long division process.
     
-    let mut pi = BigUint::one();
+    let mont = &self.montgomery;
+    let x = mont.encode(x);
+    let mut pi = mont.one.clone();
+    let mut product = pi.clone();
+    let mut scratch = vec![0; pi.len() + 1];
     let mut remainder = BigUint::zero();
     
     // We need to process t+1 bits for the number 2^t (a 1 followed by t zeros).
@@ -429,7 +674,8 @@ fn compute_wesolowski_proof(
     for i in (0..=iterations).rev() { // CORRECT: from t down to 0
         // Every step in the long division corresponds to a squaring in the exponentiation.
         // This is the "square" part of the square-and-multiply algorithm.
-        pi = self.mod_square(&pi);
+        mont.mul(&pi, &pi, &mut product, &mut scratch);
+        std::mem::swap(&mut pi, &mut product);
         
         // Bring down the next bit of the dividend (2^t).
         remainder <<= 1;
@@ -444,29 +690,30 @@ fn compute_wesolowski_proof(
             remainder -= l;
             // If it does, the quotient bit is 1. This corresponds to the "multiply"
             // part of the square-and-multiply algorithm.
-            pi = (pi * x) % &self.modulus;
+            mont.mul(&pi, &x, &mut product, &mut scratch);
+            std::mem::swap(&mut pi, &mut product);
         }
     }
     
-    Ok(pi)
+    Ok(mont.decode(&pi))
 }
     
     
     /// Verify a VDF proof
-fn verify_proof_internal(&self, input: &str, proof: &VDFProof) -> Result<bool, String> {
+fn verify_proof_internal(&self, input: &str, proof: &VDFProof) -> Result<bool, VdfError> {
     // Validate parameters
     if proof.iterations < MIN_ITERATIONS || proof.iterations > MAX_ITERATIONS {
         return Ok(false);
     }
     
     // Decode base64 values
-    let y = base64_to_biguint(&proof.y)?;
-    let pi = base64_to_biguint(&proof.pi)?;
-    let l = base64_to_biguint(&proof.l)?;
-    let r = base64_to_biguint(&proof.r)?;
+    let y = base64_to_biguint("y", &proof.y)?;
+    let pi = base64_to_biguint("pi", &proof.pi)?;
+    let l = base64_to_biguint("l", &proof.l)?;
+    let r = base64_to_biguint("r", &proof.r)?;
     
     // Verify l is a valid prime
-    if l.bits() < (SECURITY_BITS as u64 - 8) || !self.is_probable_prime(&l, 20) {
+    if l.bits() < (SECURITY_BITS as u64 - 8) || !is_probable_prime(&l, 20) {
         debug_log!("Invalid challenge prime");
         return Ok(false);
     }
@@ -509,104 +756,505 @@ fn verify_proof_internal(&self, input: &str, proof: &VDFProof) -> Result<bool, S
     Ok(y == right_side)
 }
     
-    /// Miller-Rabin primality test
-     fn is_probable_prime(&self, n: &BigUint, k: usize) -> bool {
-        if n <= &BigUint::one() {
-            return false;
+    /// Create a VDF computer with a custom modulus (hex string) - TEST ONLY
+    /// This bypasses security validations and should only be used for testing
+    #[cfg(test)]
+    pub fn with_modulus_unchecked(modulus_hex: &str) -> Result<VDFComputer, JsValue> {
+        let modulus = BigUint::parse_bytes(modulus_hex.as_bytes(), 16)
+            .ok_or_else(|| JsValue::from_str("Invalid modulus format"))?;
+        
+        // For tests, we allow any odd modulus
+        if modulus.is_even() {
+            return Err(JsValue::from_str("Modulus must be odd"));
         }
         
-        if n == &BigUint::from(2u32) || n == &BigUint::from(3u32) {
-            return true;
+        Ok(VDFComputer::from_modulus(modulus))
+    }
+    
+}
+
+impl Default for VDFComputer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Report progress to a JS callback every PROGRESS_INTERVAL iterations, when the percentage moves
+fn report_progress(on_progress: &Option<Function>, i: u64, iterations: u64, last_progress: &mut u64) {
+    if let Some(callback) = on_progress {
+        if i.is_multiple_of(PROGRESS_INTERVAL) || i == iterations - 1 {
+            let progress = ((i + 1) * 100) / iterations;
+            if progress != *last_progress {
+                *last_progress = progress;
+                let this = JsValue::null();
+                let progress_val = JsValue::from_f64(progress as f64);
+                if let Err(e) = callback.call1(&this, &progress_val) {
+                    warn_log!("Progress callback error: {:?}", e);
+                }
+            }
         }
+    }
+}
+
+/// Derive a SECURITY_BITS challenge prime from a hasher already fed the Fiat-Shamir transcript
+fn hash_to_prime(hasher: &Sha256) -> Result<BigUint, VdfError> {
+    // Use the hash directly for deterministic generation
+    for attempt in 0..1000 {
+        let mut h = hasher.clone();  // Clone the original hasher
+        h.update((attempt as u32).to_be_bytes());
+        // Don't finalize h here either - we need it for the inner loop
         
-        if n.is_even() {
-            return false;
+        // Build a SECURITY_BITS sized number from repeated hashing
+        let mut bytes = Vec::new();
+        let mut counter = 0u32;
+        while bytes.len() * 8 < SECURITY_BITS {
+            let mut h2 = h.clone();  // Clone h each time
+            h2.update(counter.to_be_bytes());
+            bytes.extend_from_slice(&h2.finalize());  // Only finalize h2
+            counter += 1;
         }
         
-        // Write n-1 as 2^r * d
-        let one = BigUint::one();
-        let two = BigUint::from(2u32);
-        let n_minus_1 = n - &one;
+        // Truncate to exact bit length
+        let bytes_needed = SECURITY_BITS.div_ceil(8);
+        bytes.truncate(bytes_needed);
         
-        let mut r = 0;
-        let mut d = n_minus_1.clone();
+        let mut candidate = BigUint::from_bytes_be(&bytes);
         
-        while d.is_even() {
-            d >>= 1;
-            r += 1;
+        // Ensure exactly SECURITY_BITS
+        if !SECURITY_BITS.is_multiple_of(8) {
+            candidate >>= 8 - (SECURITY_BITS % 8);
         }
         
-        // Witness loop with deterministic witnesses for small n
-        let witnesses: Vec<BigUint> = if n < &BigUint::from(3317044064679887385961981u128) {
-            vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37]
-                .into_iter()
-                .map(|w| BigUint::from(w as u32))
-                .collect()
-        } else {
-            // Random witnesses for large n
-            let mut rng = thread_rng();
-            (0..k)
-                .map(|_| rng.gen_biguint_range(&two, &(n - &two)))
-                .collect()
-        };
+        candidate |= BigUint::one(); // Make odd
+        candidate |= BigUint::one() << (SECURITY_BITS - 1); // Set high bit
         
-        'witness: for a in witnesses {
-            if a >= *n {
-                continue;
-            }
-            
-            let mut x = a.modpow(&d, n);  // n is already &BigUint
-            
-            if x == one || x == n_minus_1 {
+        if is_probable_prime(&candidate, 40) {
+            debug_log!("Generated challenge prime in {} attempts", attempt + 1);
+            return Ok(candidate);
+        }
+    }
+    
+    Err(VdfError::ChallengePrime)
+}
+
+/// Miller-Rabin primality test
+fn is_probable_prime(n: &BigUint, k: usize) -> bool {
+    if n <= &BigUint::one() {
+        return false;
+    }
+
+    if n == &BigUint::from(2u32) || n == &BigUint::from(3u32) {
+        return true;
+    }
+
+    if n.is_even() {
+        return false;
+    }
+
+    // Write n-1 as 2^r * d
+    let one = BigUint::one();
+    let two = BigUint::from(2u32);
+    let n_minus_1 = n - &one;
+
+    let mut r = 0;
+    let mut d = n_minus_1.clone();
+
+    while d.is_even() {
+        d >>= 1;
+        r += 1;
+    }
+
+    // Witness loop with deterministic witnesses for small n
+    let witnesses: Vec<BigUint> = if n < &BigUint::from(3317044064679887385961981u128) {
+        vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37]
+            .into_iter()
+            .map(|w| BigUint::from(w as u32))
+            .collect()
+    } else {
+        // Random witnesses for large n
+        let mut rng = thread_rng();
+        (0..k)
+            .map(|_| rng.gen_biguint_range(&two, &(n - &two)))
+            .collect()
+    };
+
+    'witness: for a in witnesses {
+        if a >= *n {
+            continue;
+        }
+
+        let mut x = a.modpow(&d, n);  // n is already &BigUint
+
+        if x == one || x == n_minus_1 {
+            continue 'witness;
+        }
+
+        for _ in 0..r - 1 {
+            x = x.modpow(&two, n);
+            if x == n_minus_1 {
                 continue 'witness;
             }
-            
-            for _ in 0..r - 1 {
-                x = x.modpow(&two, n);
-                if x == n_minus_1 {
-                    continue 'witness;
-                }
+        }
+
+        return false;
+    }
+
+    true
+}
+
+/// Check a modulus for structure that would let someone skip the sequential squarings.
+/// Returns one reason per weakness found.
+fn modulus_weaknesses(modulus: &BigUint) -> Vec<String> {
+    let mut weaknesses = Vec::new();
+    
+    if modulus.bits() < MIN_MODULUS_BITS {
+        weaknesses.push(format!("Modulus is {} bits, at least {} required", modulus.bits(), MIN_MODULUS_BITS));
+    }
+    if modulus.is_even() {
+        weaknesses.push("Modulus is even".to_string());
+    }
+    
+    if let Some((name, _)) = KNOWN_FACTORED_MODULI.iter().find(|(_, hex)| {
+        BigUint::parse_bytes(hex.as_bytes(), 16).as_ref() == Some(modulus)
+    }) {
+        weaknesses.push(format!("Modulus is {}, whose factorization is public", name));
+    }
+    
+    if let Some(p) = small_primes(TRIAL_DIVISION_LIMIT)
+        .into_iter()
+        .find(|&p| (modulus % p).is_zero() && *modulus != BigUint::from(p))
+    {
+        weaknesses.push(format!("Modulus has small factor {}", p));
+    }
+    
+    if is_probable_prime(modulus, 20) {
+        weaknesses.push("Modulus is prime, so the group order is known".to_string());
+    }
+    
+    // Fermat's method: N = a^2 - b^2 = (a - b)(a + b) for a just above sqrt(N)
+    let root = modulus.sqrt();
+    if &root * &root == *modulus {
+        weaknesses.push("Modulus is a perfect square".to_string());
+    } else {
+        let start = root + 1u32;
+        for step in 0..FERMAT_ROUNDS {
+            let a = &start + step;
+            let b_squared = &a * &a - modulus;
+            let b = b_squared.sqrt();
+            if &b * &b == b_squared {
+                weaknesses.push("Modulus factors are too close together (Fermat factorization)".to_string());
+                break;
             }
-            
-            return false;
         }
-        
-        true
     }
     
-    /// Create a VDF computer with a custom modulus (hex string) - TEST ONLY
-    /// This bypasses security validations and should only be used for testing
-    #[cfg(test)]
-    pub fn with_modulus_unchecked(modulus_hex: &str) -> Result<VDFComputer, JsValue> {
-        let modulus = BigUint::parse_bytes(modulus_hex.as_bytes(), 16)
-            .ok_or_else(|| JsValue::from_str("Invalid modulus format"))?;
-        
-        // For tests, we allow any odd modulus
-        if modulus.is_even() {
-            return Err(JsValue::from_str("Modulus must be odd"));
+    weaknesses
+}
+
+/// Primes below `limit` (sieve of Eratosthenes)
+fn small_primes(limit: usize) -> Vec<u32> {
+    let mut is_composite = vec![false; limit];
+    let mut primes = Vec::new();
+    for n in 2..limit {
+        if !is_composite[n] {
+            primes.push(n as u32);
+            for multiple in (n * n..limit).step_by(n) {
+                is_composite[multiple] = true;
+            }
         }
-        
-        // Precompute Montgomery parameters (simplified for this example)
-        let montgomery_r = BigUint::one() << modulus.bits();
-        let montgomery_r_inv = montgomery_r.clone();
-        
-        Ok(VDFComputer {
-            modulus,
-            montgomery_r,
-            montgomery_r_inv,
+    }
+    primes
+}
+
+/// Built-in modulus profiles as JSON: `[{ id, name, trust, fingerprint }]`
+#[wasm_bindgen]
+pub fn modulus_profiles() -> String {
+    let profiles: Vec<serde_json::Value> = MODULUS_PROFILES
+        .iter()
+        .map(|p| {
+            let mut entry = serde_json::to_value(p).expect("Profile serialization cannot fail");
+            entry["fingerprint"] = VDFComputer::with_profile(p.id)
+                .map(|c| c.modulus_fingerprint())
+                .unwrap_or_default()
+                .into();
+            entry
         })
+        .collect();
+    serde_json::Value::Array(profiles).to_string()
+}
+
+/// Check a modulus (hex string) for known weaknesses, returning the reasons found
+#[wasm_bindgen]
+pub fn check_modulus_strength(modulus_hex: &str) -> Result<Vec<String>, JsValue> {
+    let modulus = BigUint::parse_bytes(modulus_hex.as_bytes(), 16)
+        .ok_or_else(|| JsValue::from_str("Invalid modulus format"))?;
+    Ok(modulus_weaknesses(&modulus))
+}
+
+/// Inputs covered by the published conformance vectors
+const CONFORMANCE_INPUTS: &[(&str, u64)] = &[
+    ("0000000000000000000000000000000000000000000000000000000000000000", MIN_ITERATIONS),
+    ("bitquill conformance", MIN_ITERATIONS),
+    ("bitquill conformance", 2 * MIN_ITERATIONS + 1),
+];
+
+/// Test vectors for reimplementing BitQuill's VDF verification
+#[derive(Serialize, Deserialize)]
+struct ConformanceVectors {
+    format: String,
+    version: u32,
+    modulus_id: String,
+    modulus_fingerprint: String,
+    vectors: Vec<ConformanceVector>,
+}
+
+/// One input with every intermediate value of its proof
+#[derive(Serialize, Deserialize)]
+struct ConformanceVector {
+    input: String,
+    iterations: u64,
+    /// hash_to_group(input), hex
+    x: String,
+    proof: VDFProof,
+}
+
+/// Emit conformance vectors as JSON: hash-to-group outputs, Fiat-Shamir primes,
+/// remainders and full proofs over the given modulus profile
+#[wasm_bindgen]
+pub fn conformance_vectors(profile_id: &str) -> Result<String, JsValue> {
+    let computer = VDFComputer::with_profile(profile_id)?;
+    let mut vectors = Vec::new();
+    for (input, iterations) in CONFORMANCE_INPUTS {
+        vectors.push(ConformanceVector {
+            input: input.to_string(),
+            iterations: *iterations,
+            x: computer.hash_to_group(input)?.to_str_radix(16),
+            proof: computer.compute_proof_internal(input, *iterations, None)?,
+        });
+    }
+    let document = ConformanceVectors {
+        format: "bitquill-vdf-vectors".to_string(),
+        version: 1,
+        modulus_id: profile_id.to_string(),
+        modulus_fingerprint: computer.modulus_fingerprint(),
+        vectors,
+    };
+    serde_json::to_string_pretty(&document).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Check conformance vectors against this implementation, returning every mismatch found
+#[wasm_bindgen]
+pub fn check_conformance_vectors(json: &str) -> Result<Vec<String>, JsValue> {
+    let document: ConformanceVectors = serde_json::from_str(json)
+        .map_err(|e| JsValue::from_str(&format!("Invalid conformance vectors: {}", e)))?;
+    let computer = VDFComputer::with_profile(&document.modulus_id)?;
+    let mut mismatches = Vec::new();
+    if computer.modulus_fingerprint() != document.modulus_fingerprint {
+        mismatches.push(format!("Modulus fingerprint differs for profile {}", document.modulus_id));
+    }
+    for (i, vector) in document.vectors.iter().enumerate() {
+        let x = computer.hash_to_group(&vector.input)?;
+        if x.to_str_radix(16) != vector.x {
+            mismatches.push(format!("Vector {}: hash_to_group output differs", i));
+            continue;
+        }
+        if vector.proof.iterations != vector.iterations {
+            mismatches.push(format!("Vector {}: proof iterations differ", i));
+        }
+        let y = base64_to_biguint("y", &vector.proof.y)?;
+        let l = computer.generate_fiat_shamir_prime(&x, &y, vector.iterations)?;
+        if general_purpose::STANDARD.encode(l.to_bytes_be()) != vector.proof.l {
+            mismatches.push(format!("Vector {}: Fiat-Shamir prime differs", i));
+        }
+        let r = computer.compute_remainder(vector.iterations, &l)?;
+        if general_purpose::STANDARD.encode(r.to_bytes_be()) != vector.proof.r {
+            mismatches.push(format!("Vector {}: remainder differs", i));
+        }
+        if !computer.verify_proof_internal(&vector.input, &vector.proof)? {
+            mismatches.push(format!("Vector {}: proof does not verify", i));
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Fields of an epoch covered by its hash; mirrors `epochHashContent` in main.js
+const EPOCH_HASH_FIELDS: &[&str] = &["epochNumber", "previousHash", "deltas", "iterations"];
+const EPOCH_OPTIONAL_HASH_FIELDS: &[&str] = &["difficultyChange", "resumedFrom", "events", "declaredRate", "cadenceHash", "seeded", "hashVersion"];
+
+fn epoch_hash_content(epoch: &serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+    let mut content = serde_json::Map::new();
+    for field in EPOCH_HASH_FIELDS {
+        content.insert(field.to_string(), epoch.get(*field).cloned().unwrap_or(serde_json::Value::Null));
+    }
+    content.insert("vdfY".to_string(), epoch["vdfProof"]["y"].clone());
+    for field in EPOCH_OPTIONAL_HASH_FIELDS {
+        if let Some(value) = epoch.get(*field) {
+            content.insert(field.to_string(), value.clone());
+        }
+    }
+    content
+}
+
+/// Hash of an epoch in its canonical encoding (hashVersion 1), hex.
+/// serde_json maps are sorted by key, so `to_string` yields the same text as `canonicalJson`.
+fn epoch_hash(epoch: &serde_json::Value) -> String {
+    let encoded = serde_json::Value::Object(epoch_hash_content(epoch)).to_string();
+    Sha256::digest(encoded.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// SHA3-256 digest of an epoch in dual-hash mode: the same content as its
+/// SHA-256 hash plus the previous epoch's SHA3 digest, forming a second chain
+/// that does not depend on SHA-256. Mirrors `epochHashSha3` in main.js.
+fn epoch_hash_sha3(epoch: &serde_json::Value, previous: &serde_json::Value) -> String {
+    let mut content = epoch_hash_content(epoch);
+    content.insert("previousHashSha3".to_string(), previous.get("hashSha3").cloned().unwrap_or(serde_json::Value::Null));
+    sha3_256_hex(&serde_json::Value::Object(content).to_string())
+}
+
+/// SHA3-256 (FIPS 202) of a UTF-8 string, hex. Dual-hash documents chain it next
+/// to SHA-256 so their hashes stay verifiable if SHA-256 is broken.
+#[wasm_bindgen]
+pub fn sha3_256_hex(data: &str) -> String {
+    Sha3_256::digest(data.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Result of `VDFComputer::explain_proof`; big numbers are hex
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProofExplanation {
+    modulus_fingerprint: String,
+    modulus_bits: u64,
+    iterations: u64,
+    input: String,
+    /// hash_to_group(input)
+    x: String,
+    y: String,
+    pi: String,
+    l: String,
+    r: String,
+    l_is_prime: bool,
+    /// l recomputed from the Fiat-Shamir hash of (x, y, iterations, N)
+    expected_l: String,
+    l_matches_fiat_shamir: bool,
+    /// r == 2^iterations mod l
+    r_matches: bool,
+    /// pi^l mod N
+    pi_l: String,
+    /// x^r mod N
+    x_r: String,
+    /// pi^l * x^r mod N, which must equal y
+    product: String,
+    holds: bool,
+}
+
+/// Verify the proof chain of an exported document (JSON) without the web app:
+/// chain linkage, epoch hashes and every VDF proof. Signatures are not checked.
+#[wasm_bindgen]
+pub fn verify_chain_export(json: &str) -> Result<String, JsValue> {
+    let report = verify_chain_report(json, VerifyLevel::Chain)?;
+    serde_json::to_string(&report).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// The VDF a document was written with, named by its provenance `vdfBackend`
+enum VdfBackend {
+    Rsa(&'static ModulusProfile, VDFComputer),
+    ClassGroup(ClassGroupVDF),
+}
+
+impl VdfBackend {
+    fn for_document(document: &serde_json::Value) -> Result<VdfBackend, String> {
+        let provenance = &document["provenance"];
+        match provenance["vdfBackend"].as_str().unwrap_or("wesolowski-rsa") {
+            "wesolowski-rsa" => {
+                let modulus_id = provenance["modulusId"].as_str().unwrap_or("rsa-2048");
+                let computer = VDFComputer::from_profile(modulus_id)?;
+                let profile = MODULUS_PROFILES.iter().find(|p| p.id == modulus_id).expect("from_profile found it");
+                Ok(VdfBackend::Rsa(profile, computer))
+            }
+            "wesolowski-class-group" => Ok(VdfBackend::ClassGroup(ClassGroupVDF::new())),
+            other => Err(format!("Unsupported VDF backend: {}", other)),
+        }
+    }
+
+    /// Reasons the group cannot back a time proof, as the web app reports them
+    fn setup_errors(&self) -> Vec<String> {
+        match self {
+            VdfBackend::Rsa(profile, computer) => {
+                let mut errors = Vec::new();
+                if profile.trust != "trusted" {
+                    errors.push(format!("Modulus: \"{}\" is a {} modulus, not a trusted one.", profile.name, profile.trust));
+                }
+                errors.extend(computer.modulus_weaknesses().into_iter().map(|weakness| format!("Modulus: {}.", weakness)));
+                errors
+            }
+            VdfBackend::ClassGroup(_) => Vec::new(),
+        }
+    }
+
+    fn verify_proof(&self, input: &str, proof: &VDFProof) -> Result<bool, VdfError> {
+        match self {
+            VdfBackend::Rsa(_, computer) => computer.verify_proof_internal(input, proof),
+            VdfBackend::ClassGroup(vdf) => vdf.verify_proof_internal(input, proof),
+        }
+    }
+}
+
+/// SHA-256 of a document as `computeDocumentHash` in main.js encodes it: signing
+/// fields blanked, custody left out, and for 2.2-crypto documents without epoch
+/// cadence and `provenance.environment`. None for the legacy encoding.
+fn document_hash(document: &serde_json::Value) -> Option<String> {
+    if document["hashVersion"].as_u64() != Some(1) {
+        return None;
+    }
+    let mut content = document.as_object()?.clone();
+    content.remove("custody");
+    if document["version"] == "2.2-crypto" {
+        if let Some(provenance) = content.get_mut("provenance").and_then(|p| p.as_object_mut()) {
+            provenance.remove("environment");
+        }
+        for epoch in content.get_mut("proofChain")?.as_array_mut()? {
+            if let Some(epoch) = epoch.as_object_mut() {
+                epoch.remove("cadence");
+            }
+        }
+    }
+    let metadata = content.get_mut("metadata")?.as_object_mut()?;
+    for field in ["documentHash", "publicKey", "signature"] {
+        metadata.insert(field.to_string(), serde_json::Value::Null);
+    }
+    metadata.remove("signatureSha3");
+    let encoded = serde_json::Value::Object(content).to_string();
+    Some(Sha256::digest(encoded.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Epoch numbering and session restarts, as `checkSessionLinkage` in main.js
+fn check_session_linkage(epoch: &serde_json::Value, previous: &serde_json::Value, index: usize, report: &mut ChainReport) {
+    if epoch["epochNumber"].as_u64() != Some(index as u64) {
+        report.errors.push(format!("Epoch {}: Out-of-sequence epoch number {}.", index, epoch["epochNumber"]));
+    }
+    let resumed = &epoch["resumedFrom"];
+    if resumed.is_null() {
+        return;
+    }
+    if resumed["epochNumber"] != previous["epochNumber"] || resumed["hash"] != previous["hash"] {
+        report.errors.push(format!("Epoch {}: Session restart does not link to the previous session.", index));
+    }
+    // ISO 8601 UTC timestamps as toISOString writes them sort as text
+    if let (Some(at), Some(before)) = (epoch["timestamp"].as_str(), previous["timestamp"].as_str()) {
+        if at < before {
+            report.errors.push(format!("Epoch {}: Session restart predates the previous session.", index));
+        }
     }
-    
 }
 
 /// Helper function to decode base64 to BigUint
-fn base64_to_biguint(b64: &str) -> Result<BigUint, String> {
+fn base64_to_biguint(field: &'static str, b64: &str) -> Result<BigUint, VdfError> {
     let bytes = general_purpose::STANDARD
         .decode(b64)
-        .map_err(|e| format!("Base64 decode error: {}", e))?;
+        .map_err(|e| VdfError::Decode { field, reason: format!("Base64 decode error: {}", e) })?;
     
     if bytes.is_empty() {
-        return Err("Empty bytes".to_string());
+        return Err(VdfError::Decode { field, reason: "Empty bytes".to_string() });
     }
     
     Ok(BigUint::from_bytes_be(&bytes))
@@ -650,6 +1298,7 @@ pub fn get_version() -> String {
 // ===================================================================================
 
 #[cfg(test)]
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))] // wasm_bindgen_test only runs under wasm-pack
 mod tests {
     use super::*;
     use wasm_bindgen_test::*;
@@ -659,7 +1308,7 @@ mod tests {
 
 
 /// Real 512-bit RSA modulus generated with OpenSSL
-const TEST_MODULUS_HEX: &str = "bc975c587f80c63fc038828ed7416a2c0cf209e434494b77096086f47cbafff224d6c853998f3cfb8a8fd1c847b06666561e8ef5adfe5b3e11c09ac7324c4119";
+const TEST_MODULUS_HEX: &str = TEST_512_MODULUS;
 
     /// Creates a VDF computer with the default RSA-2048 modulus.
     fn setup_default_computer() -> VDFComputer {
@@ -696,6 +1345,170 @@ fn test_with_custom_modulus() {
     assert_eq!(computer.modulus.bits(), 512, "Test modulus should be 512 bits");
 }
 
+    #[wasm_bindgen_test]
+    fn test_modulus_fingerprint_identifies_modulus() {
+        let default_fingerprint = setup_default_computer().modulus_fingerprint();
+        let test_fingerprint = setup_test_computer().modulus_fingerprint();
+
+        assert_eq!(default_fingerprint.len(), 64, "Fingerprint should be a hex SHA-256 digest");
+        assert_eq!(default_fingerprint, setup_default_computer().modulus_fingerprint());
+        assert_ne!(default_fingerprint, test_fingerprint, "Different moduli should have different fingerprints");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_modulus_strength_checks() {
+        assert!(setup_default_computer().modulus_weaknesses().is_empty(), "RSA-2048 should pass");
+
+        let rsa_100 = check_modulus_strength(KNOWN_FACTORED_MODULI[0].1).unwrap();
+        assert!(rsa_100.iter().any(|w| w.contains("RSA-100")), "Factored challenge numbers should be flagged");
+
+        let small_factor = BigUint::parse_bytes(RSA_2048_MODULUS.as_bytes(), 16).unwrap() * 7u32;
+        let weaknesses = check_modulus_strength(&small_factor.to_str_radix(16)).unwrap();
+        assert!(weaknesses.iter().any(|w| w.contains("small factor 7")), "Small factors should be flagged");
+
+        // Product of two consecutive primes just above 2^520
+        let close_factors = "10000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000510000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000006210f";
+        let weaknesses = check_modulus_strength(close_factors).unwrap();
+        assert!(weaknesses.iter().any(|w| w.contains("Fermat")), "Close factors should be flagged");
+        assert!(VDFComputer::with_modulus(close_factors).is_err(), "Weak moduli should be rejected");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_modulus_profiles() {
+        let default_profile = VDFComputer::with_profile("rsa-2048").unwrap();
+        assert_eq!(default_profile.modulus, setup_default_computer().modulus);
+        assert_eq!(VDFComputer::with_profile("test-512").unwrap().modulus.bits(), 512);
+        assert!(VDFComputer::with_profile("no-such-profile").is_err(), "Unknown profiles should be rejected");
+
+        let profiles: serde_json::Value = serde_json::from_str(&modulus_profiles()).unwrap();
+        assert_eq!(profiles[0]["id"], "rsa-2048");
+        assert_eq!(profiles[0]["fingerprint"], default_profile.modulus_fingerprint().as_str());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_conformance_vectors_round_trip() {
+        let json = conformance_vectors("test-512").unwrap();
+        assert!(check_conformance_vectors(&json).unwrap().is_empty(), "Fresh vectors should conform");
+
+        let mut document: serde_json::Value = serde_json::from_str(&json).unwrap();
+        document["vectors"][1]["input"] = "tampered".into();
+        let mismatches = check_conformance_vectors(&document.to_string()).unwrap();
+        assert_eq!(mismatches, vec!["Vector 1: hash_to_group output differs".to_string()]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_epoch_hash_matches_web_app() {
+        // Hash computed by epochHash in main.js for the same epoch
+        let epoch = serde_json::json!({
+            "epochNumber": 1, "previousHash": "00", "deltas": [{ "ops": [{ "insert": "Hello\n" }] }],
+            "vdfProof": { "y": "AQ==" }, "iterations": 1000, "events": [{ "type": "undo", "delta": 0 }],
+            "hashVersion": 1, "timestamp": "2024-01-01T00:00:00.000Z"
+        });
+        assert_eq!(epoch_hash(&epoch), "d76edcbf0c1b0c4dc8b28f0aa74825387135d9d1c2d6915149ec2c67f1274b3c");
+        // And by epochHashSha3, first without and then with a previous SHA3 digest
+        assert_eq!(epoch_hash_sha3(&epoch, &serde_json::json!({})), "86d91a8867b3740eb8a404c4ff109c3027edc09f70a4ddba9ae95befe17a70f0");
+        assert_eq!(epoch_hash_sha3(&epoch, &serde_json::json!({ "hashSha3": "ab" })), "243f7b83bdcde767694e6286b4d49c413873ae0c72b0828e6321f5bf68818d37");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_document_hash_matches_web_app() {
+        // Hashes computed by computeDocumentHash in main.js for the same document
+        let mut document = serde_json::json!({
+            "title": "T", "version": "2.2-crypto", "hashVersion": 1, "content": { "html": "<p>Hi</p>" },
+            "proofChain": [{ "epochNumber": 0, "hash": "00" },
+                { "epochNumber": 1, "hash": "ab", "cadence": { "bucketsMs": [50], "counts": [1, 2] }, "cadenceHash": "cd" }],
+            "provenance": { "appVersion": "1.0", "environment": { "userAgent": "UA" } },
+            "custody": [{ "from": "x" }],
+            "metadata": { "epochCount": 2, "documentHash": "ignored", "publicKey": { "kty": "EC" }, "signature": "sig", "signatureSha3": "s3" }
+        });
+        assert_eq!(document_hash(&document).unwrap(), "42b07c295e7481794f967804233f852282fce8bbbd2357294c899f796df154d0");
+        document["version"] = "2.1-crypto".into();
+        assert_eq!(document_hash(&document).unwrap(), "4b402c40188e7be9fe9fe189b72e46de5cedf58e6da41d4a85f71014d636b430");
+        document.as_object_mut().unwrap().remove("hashVersion");
+        assert_eq!(document_hash(&document), None, "Legacy encoding is not reproduced");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_sha3_256_known_answers() {
+        // FIPS 202 examples; 135 and 136 bytes straddle the rate boundary
+        assert_eq!(sha3_256_hex(""), "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a");
+        assert_eq!(sha3_256_hex("abc"), "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532");
+        assert_eq!(sha3_256_hex(&"a".repeat(135)), "8094bb53c44cfb1e67b7c30447f9a1c33696d2463ecc1d9c92538913392843c9");
+        assert_eq!(sha3_256_hex(&"a".repeat(136)), "3fc5559f14db8e453a0a3091edbd2bc25e11528d81c66fa570a4efdcc2695ee1");
+    }
+
+    /// A one-epoch document whose proof was computed with `computer`
+    fn chain_document(computer: &VDFComputer, modulus_id: &str) -> serde_json::Value {
+        let genesis = "0".repeat(64);
+        let proof = computer.compute_proof_internal(&genesis, MIN_ITERATIONS, None).unwrap();
+        let mut epoch = serde_json::json!({
+            "epochNumber": 1, "previousHash": genesis, "deltas": [{ "ops": [{ "insert": "Hi" }] }],
+            "vdfProof": { "y": proof.y, "pi": proof.pi, "l": proof.l, "r": proof.r },
+            "iterations": MIN_ITERATIONS, "hashVersion": 1
+        });
+        epoch["hash"] = epoch_hash(&epoch).into();
+        let mut document = serde_json::json!({
+            "title": "Chain", "version": "2.2-crypto", "hashVersion": 1,
+            "provenance": { "modulusId": modulus_id },
+            "proofChain": [{ "epochNumber": 0, "hash": genesis }, epoch],
+            "metadata": { "epochCount": 2, "documentHash": null, "publicKey": null, "signature": null }
+        });
+        document["metadata"]["documentHash"] = document_hash(&document).into();
+        document
+    }
+
+    #[wasm_bindgen_test]
+    fn test_verify_chain_export() {
+        let mut document = chain_document(&setup_default_computer(), "rsa-2048");
+
+        let report: serde_json::Value = serde_json::from_str(&verify_chain_export(&document.to_string()).unwrap()).unwrap();
+        assert_eq!(report["valid"], true, "Untouched chain should verify: {}", report);
+        assert_eq!(report["verifiedEpochs"], 1);
+
+        let report = verify_chain_report(&document.to_string(), VerifyLevel::Full).unwrap();
+        assert!(report.valid() && !report.signature_checked(), "{:?}", report.errors());
+        let mut retitled = document.clone();
+        retitled["title"] = "Another".into();
+        let report = verify_chain_report(&retitled.to_string(), VerifyLevel::Full).unwrap();
+        assert_eq!(report.errors(), ["Document content does not match its signed hash."], "Full level checks the document hash");
+        assert!(verify_chain_report(&retitled.to_string(), VerifyLevel::Chain).unwrap().valid(), "Chain level does not");
+
+        document["provenance"]["seeded"] = "demo".into();
+        let report: serde_json::Value = serde_json::from_str(&verify_chain_export(&document.to_string()).unwrap()).unwrap();
+        assert!(report["warnings"][0].as_str().unwrap().starts_with("Seeded demo document"), "Seeded documents are flagged: {}", report);
+
+        document["proofChain"][1]["deltas"][0]["ops"][0]["insert"] = "Bye".into();
+        let report: serde_json::Value = serde_json::from_str(&verify_chain_export(&document.to_string()).unwrap()).unwrap();
+        assert_eq!(report["errors"], serde_json::json!(["Epoch 1: Hash mismatch."]));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_verify_level_parses_tool_names() {
+        assert_eq!("chain".parse::<VerifyLevel>(), Ok(VerifyLevel::Chain));
+        assert_eq!("full".parse::<VerifyLevel>(), Ok(VerifyLevel::Full));
+        assert!("Full".parse::<VerifyLevel>().is_err(), "Level names are lower case");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_verify_chain_export_rejects_untrusted_modulus() {
+        let document = chain_document(&setup_test_computer(), "test-512");
+        let report = verify_chain_report(&document.to_string(), VerifyLevel::Chain).unwrap();
+        assert!(!report.valid, "A modulus with public factors proves nothing");
+        assert_eq!(report.verified_epochs, 1, "The proof itself still checks out");
+        assert!(report.errors.iter().any(|e| e.contains("not a trusted one")), "{:?}", report.errors);
+        assert!(report.errors.iter().any(|e| e.contains(&format!("at least {} required", MIN_MODULUS_BITS))), "{:?}", report.errors);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_set_log_level() {
+        set_log_level("debug").unwrap();
+        assert!(log_enabled(LOG_DEBUG));
+        set_log_level("error").unwrap();
+        assert!(!log_enabled(LOG_WARN) && log_enabled(LOG_ERROR));
+        assert!(set_log_level("verbose").is_err());
+        set_log_level("warn").unwrap();
+    }
+
     #[wasm_bindgen_test]
     fn test_proof_verification_fails_with_wrong_input() {
         let computer = setup_default_computer();
@@ -726,6 +1539,40 @@ fn test_with_custom_modulus() {
         assert!(!is_valid, "Verification should fail if 'y' is tampered");
     }
 
+    #[wasm_bindgen_test]
+    fn test_explain_proof_shows_the_equation() {
+        let computer = setup_default_computer();
+        let input = "explain proof test";
+        let mut proof = computer.compute_proof(input, MIN_ITERATIONS, None).unwrap();
+
+        let explanation: serde_json::Value = serde_json::from_str(&computer.explain_proof(input, &proof).unwrap()).unwrap();
+        assert_eq!(explanation["holds"], true);
+        assert_eq!(explanation["lMatchesFiatShamir"], true);
+        assert_eq!(explanation["product"], explanation["y"]);
+
+        proof.r = general_purpose::STANDARD.encode([3u8]);
+        let explanation: serde_json::Value = serde_json::from_str(&computer.explain_proof(input, &proof).unwrap()).unwrap();
+        assert_eq!(explanation["rMatches"], false);
+        assert_eq!(explanation["holds"], false);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_malformed_proof_errors_name_the_field() {
+        let computer = setup_default_computer();
+        let input = "malformed proof test";
+        let mut proof = computer.compute_proof(input, MIN_ITERATIONS, None).unwrap();
+        proof.pi = "not base64!".to_string();
+
+        match computer.verify_proof_internal(input, &proof) {
+            Err(VdfError::Decode { field, .. }) => assert_eq!(field, "pi"),
+            other => panic!("Expected a decode error for pi, got {:?}", other),
+        }
+        assert_eq!(
+            computer.compute_proof_internal(input, 1, None).unwrap_err(),
+            VdfError::IterationsOutOfRange(1)
+        );
+    }
+
     #[wasm_bindgen_test]
     fn test_iteration_bounds() {
         let computer = setup_default_computer();
@@ -768,6 +1615,23 @@ fn test_with_custom_modulus() {
         assert!(is_valid, "Deserialized proof should be valid");
     }
 
+    #[wasm_bindgen_test]
+    fn test_montgomery_squaring_matches_modpow() {
+        let mut rng = thread_rng();
+        for modulus_hex in [RSA_2048_MODULUS, TEST_512_MODULUS, "ffffffffffffffffffffffffffffffff61"] {
+            let modulus = BigUint::parse_bytes(modulus_hex.as_bytes(), 16).unwrap();
+            let computer = VDFComputer::from_modulus(modulus.clone());
+            let l = BigUint::from(13u32);
+            for _ in 0..20 {
+                let x = rng.gen_biguint_below(&modulus);
+                let y = computer.compute_vdf_output(&x, 37, &None).unwrap();
+                assert_eq!(y, x.modpow(&(BigUint::one() << 37), &modulus));
+                let pi = computer.compute_wesolowski_proof(&x, 37, &l).unwrap();
+                assert_eq!(pi, x.modpow(&((BigUint::one() << 37) / &l), &modulus));
+            }
+        }
+    }
+
     #[wasm_bindgen_test]
     fn test_hash_to_group_is_deterministic() {
         let computer = setup_default_computer();
@@ -1,24 +1,26 @@
-// Import the WASM initializer and the VDFComputer class
-import init, { VDFComputer } from './wasm/vdf_wasm.js';
+// Import the WASM initializer and the VDF classes
+import init, { ClassGroupVDF, VDFComputer, set_log_level } from './wasm/vdf_wasm.js';
 
 // This variable will hold the initialized WASM module's exports
 let wasm;
 
 // Listen for messages from the main thread
 self.onmessage = async (event) => {
-  const { command, input, iterations } = event.data;
+  const { command, backend, input, iterations, logLevel = 'warn' } = event.data;
+  const debug = logLevel === 'debug';
 
   if (command === 'start') {
     // Initialize WASM if it hasn't been already
     if (!wasm) {
-      console.log('Worker: Initializing WASM...');
+      if (debug) console.log('Worker: Initializing WASM...');
       wasm = await init();
     }
+    set_log_level(logLevel);
     
-    console.log(`Worker: Starting VDF computation with ${iterations} iterations...`);
+    if (debug) console.log(`Worker: Starting VDF computation with ${iterations} iterations...`);
     
-    // Create the VDF computer
-    const computer = new VDFComputer();
+    // Create the VDF computer for the document's backend
+    const computer = backend === 'wesolowski-class-group' ? new ClassGroupVDF() : new VDFComputer();
     
     // Define the progress callback function
     const onProgress = (progress) => {
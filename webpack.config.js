@@ -1,7 +1,18 @@
 const path = require('path');
+const { execSync } = require('child_process');
+const webpack = require('webpack');
 const CopyPlugin = require('copy-webpack-plugin');
 const HtmlWebpackPlugin = require('html-webpack-plugin');
 
+// Short commit of the build, recorded in every saved document's provenance
+function buildCommit() {
+  try {
+    return execSync('git rev-parse --short HEAD').toString().trim();
+  } catch (e) {
+    return 'unknown';
+  }
+}
+
 module.exports = {
   // Tell webpack where to start
   entry: './main.js',
@@ -44,6 +55,10 @@ module.exports = {
     new HtmlWebpackPlugin({
         // This tells the plugin to use your existing index.html as a template
         template: 'index.html' 
+    }),
+    new webpack.DefinePlugin({
+      __APP_VERSION__: JSON.stringify(require('./package.json').version),
+      __BUILD_COMMIT__: JSON.stringify(buildCommit())
     })
   ],
   